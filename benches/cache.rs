@@ -1,19 +1,26 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use qwikache::Cache;
+use qwikache::{Cache, CacheBuilder};
 use std::time::{Duration, Instant};
 
+const ENTRIES: usize = 10_000_000;
+
+fn keys() -> Vec<String> {
+    (0..ENTRIES).map(|i| format!("test_key_{}", i)).collect()
+}
+
 fn get_expired(c: &mut Criterion) {
+    let keys = keys();
     let mut cache = Cache::default();
     let now = Instant::now();
-    for i in 0..10_000_000 {
-        let exp = now + Duration::from_secs(1000 + i % 1000);
-        cache.put_exp(format!("test_key_{}", i), "test_value", Some(exp));
+    for (i, key) in keys.iter().enumerate() {
+        let exp = now + Duration::from_secs(1000 + i as u64 % 1000);
+        cache.put_exp(key.clone(), "test_value", Some(exp));
     }
 
     let mut i = 0;
     c.bench_function("get_expired", |b| {
         b.iter(|| {
-            let cached = cache.get(&format!("test_key_{}", i));
+            let cached = cache.get(keys[i % ENTRIES].as_str());
             i += 1;
             black_box(cached.is_some());
         })
@@ -21,20 +28,48 @@ fn get_expired(c: &mut Criterion) {
 }
 
 fn get_unexpired(c: &mut Criterion) {
+    let keys = keys();
     let mut cache = Cache::default();
-    for i in 0..10_000_000 {
-        cache.put(format!("test_key_{}", i), "test_value");
+    for key in &keys {
+        cache.put(key.clone(), "test_value");
     }
 
     let mut i = 0;
     c.bench_function("get_unexpired", |b| {
         b.iter(|| {
-            let cached = cache.get(&format!("test_key_{}", i));
+            let cached = cache.get(keys[i % ENTRIES].as_str());
             i += 1;
             black_box(cached.is_some());
         })
     });
 }
 
-criterion_group!(benches, get_expired, get_unexpired);
+fn put_expiring(c: &mut Criterion) {
+    let mut group = c.benchmark_group("put_expiring");
+    for &(name, timing_wheel) in &[("ordered", false), ("timing_wheel", true)] {
+        let mut cache = CacheBuilder::new().timing_wheel(timing_wheel).build();
+        let now = Instant::now();
+        for i in 0..10_000_000 {
+            let exp = now + Duration::from_secs(1000 + i % 1000);
+            cache.put_exp(format!("test_key_{}", i), "test_value", Some(exp));
+        }
+
+        let mut i = 0;
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let exp = Instant::now() + Duration::from_secs(1000 + i % 1000);
+                cache.put_exp(
+                    format!("test_key_{}", i % 10_000_000),
+                    "test_value",
+                    Some(exp),
+                );
+                i += 1;
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, get_expired, get_unexpired, put_expiring);
 criterion_main!(benches);
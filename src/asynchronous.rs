@@ -0,0 +1,547 @@
+//! Provides a cache for use in asynchronous code running on Tokio,
+//! available with the `tokio` feature.
+
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::fmt;
+use std::future::Future;
+use std::hash::{BuildHasher, Hash};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, Weak};
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::{Duration, Instant};
+
+use tokio::sync::RwLock;
+
+use crate::clock::{Clock, SystemClock};
+use crate::flight::{self, Outcome, Takeoff, Waiters};
+use crate::policy::{EvictionPolicy, Lru};
+use crate::Cache;
+
+type LockedCache<K, V, P, S, C> = RwLock<Cache<K, V, P, S, C>>;
+
+/// Asynchronous loads in progress, by key.
+type Flights<K, V> = flight::Flights<K, V, Arc<Load>>;
+
+/// Asynchronous load of a key, awaited by one or more tasks.
+type Flight<V> = flight::Flight<V, Arc<Load>>;
+
+/// Key/value cache whose values may be computed by asynchronous functions,
+/// for use within async tasks running on Tokio.
+///
+/// Wraps an instance of [Cache] in a [tokio::sync::RwLock], so that tasks waiting
+/// for access to the cache yield to the executor rather than block its thread.
+/// The lock is never held across the `.await` of a value being computed.
+/// Clones share the same underlying cache.
+#[derive(Debug)]
+pub struct AsyncCache<K, V, P = Lru, S = RandomState, C = SystemClock> {
+    cache: Arc<LockedCache<K, V, P, S, C>>,
+    flights: Arc<Flights<K, V>>,
+}
+
+impl<K, V, P, S, C> Clone for AsyncCache<K, V, P, S, C> {
+    fn clone(&self) -> Self {
+        Self {
+            cache: self.cache.clone(),
+            flights: self.flights.clone(),
+        }
+    }
+}
+
+impl<K, V> Default for AsyncCache<K, V> {
+    fn default() -> Self {
+        Cache::default().into()
+    }
+}
+
+impl<K, V, P, S, C> From<Cache<K, V, P, S, C>> for AsyncCache<K, V, P, S, C> {
+    fn from(cache: Cache<K, V, P, S, C>) -> Self {
+        Self {
+            cache: Arc::new(RwLock::new(cache)),
+            flights: Arc::default(),
+        }
+    }
+}
+
+impl<K, V, P, S, C> AsyncCache<K, V, P, S, C>
+where
+    K: Eq + Hash,
+    V: Clone,
+    P: EvictionPolicy,
+    S: BuildHasher,
+    C: Clock,
+{
+    /// Returns a clone of the cached value for the given key, if present and not expired.
+    pub async fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.cache.read().await.get(key).cloned()
+    }
+
+    /// Stores a value for the given key, as if by [Cache::put].
+    pub async fn put(&self, key: K, value: V) {
+        write(&self.cache, |cache| cache.put(key, value)).await;
+    }
+
+    /// Stores a value for the given key, expiring after the given time-to-live.
+    pub async fn put_ttl(&self, key: K, value: V, ttl: Duration) {
+        write(&self.cache, |cache| cache.put_ttl(key, value, ttl)).await;
+    }
+
+    /// Deletes any cached value for the given key.
+    pub async fn delete<Q>(&self, key: &Q)
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        write(&self.cache, |cache| cache.delete(key)).await;
+    }
+
+    /// Returns a clone of the cached value for the given key, if present and not expired,
+    /// or else awaits the future returned by the given function and stores its value,
+    /// as if by [Cache::put]; see [AsyncCache::get_or_try_insert_with].
+    pub async fn get_or_insert_with<F, Fut>(&self, key: K, init: F) -> V
+    where
+        K: Clone + Send + Sync + 'static,
+        V: Send + Sync + 'static,
+        P: Send + Sync + 'static,
+        S: Send + Sync + 'static,
+        C: Send + Sync + 'static,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = V> + Send + 'static,
+    {
+        let init = || {
+            let init = init();
+            async move { Ok::<_, Infallible>(init.await) }
+        };
+        match self.get_or_try_insert_with(key, init).await {
+            Ok(value) => value,
+            Err(error) => match error {},
+        }
+    }
+
+    /// Returns a clone of the cached value for the given key, if present and not expired,
+    /// or else awaits the future returned by the given function and stores its value,
+    /// as if by [Cache::put].
+    ///
+    /// Concurrent calls for the same key are coalesced: the future of the first call is
+    /// shared with the others, which await it without calling their own functions.
+    /// Any of the awaiting tasks may poll the future, which thus keeps making progress
+    /// when the task that called the function is cancelled. If the future fails, its error
+    /// is returned to all of them, and nothing is stored, so the next call awaits a new future.
+    /// The future is dropped once every task awaiting it has been cancelled, so that
+    /// no work remains.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the future panics, in the task polling it as well as in the one
+    /// that called the function; the other tasks await a new future.
+    pub async fn get_or_try_insert_with<F, Fut, E>(&self, key: K, init: F) -> Result<V, E>
+    where
+        K: Clone + Send + Sync + 'static,
+        V: Send + Sync + 'static,
+        P: Send + Sync + 'static,
+        S: Send + Sync + 'static,
+        C: Send + Sync + 'static,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<V, E>> + Send + 'static,
+        E: Clone + Send + Sync + 'static,
+    {
+        if let Some(value) = self.get(&key).await {
+            return Ok(value);
+        }
+
+        let mut init = Some(init);
+        loop {
+            let flight = match self.flights.take_off(&key, || None) {
+                Takeoff::Cached(value) => return Ok(value),
+                Takeoff::Joined(flight) => flight,
+                Takeoff::Leading(landing) => {
+                    // The previous flight may have stored the value in the meantime,
+                    // in which case it is not loaded again.
+                    let (cached, started) = {
+                        let cache = self.cache.read().await;
+                        (cache.get(&key).cloned(), cache.clock().now())
+                    };
+                    if let Some(value) = cached {
+                        landing.land(Outcome::Loaded(value.clone()));
+                        return Ok(value);
+                    }
+
+                    let init = init.take().expect("load of the key panicked");
+                    let flight = landing.flight().clone();
+                    let cache = self.cache.clone();
+                    let key = key.clone();
+                    let load = init();
+                    flight.waiters().start(async move {
+                        let outcome = match load.await {
+                            Ok(value) => {
+                                store_loaded(&cache, key, value.clone(), started).await;
+                                Outcome::Loaded(value)
+                            }
+                            Err(error) => Outcome::Failed(Arc::new(error)),
+                        };
+
+                        landing.land(outcome);
+                    });
+
+                    flight
+                }
+            };
+
+            match Join::new(flight).await {
+                Outcome::Loaded(value) => return Ok(value),
+                Outcome::Failed(error) => match error.downcast_ref::<E>() {
+                    Some(error) => return Err(error.clone()),
+                    // The future of another call failed with an error of another type.
+                    None => continue,
+                },
+                Outcome::Abandoned => continue,
+            }
+        }
+    }
+}
+
+/// Performs the given write operation under an exclusive lock, then notifies
+/// the cache's removal listener, if any, of the removed items once it is released.
+async fn write<K, V, P, S, C, T, F>(cache: &LockedCache<K, V, P, S, C>, write: F) -> T
+where
+    F: FnOnce(&mut Cache<K, V, P, S, C>) -> T,
+{
+    let (result, removals) = {
+        let mut cache = cache.write().await;
+        let result = write(&mut cache);
+        (result, cache.take_removals())
+    };
+
+    removals.notify();
+    result
+}
+
+/// Stores a loaded value as if by [Cache::put], and records the time elapsed
+/// since its load started.
+async fn store_loaded<K, V, P, S, C>(
+    cache: &LockedCache<K, V, P, S, C>,
+    key: K,
+    value: V,
+    started: Instant,
+) where
+    K: Eq + Hash + Clone,
+    P: EvictionPolicy,
+    S: BuildHasher,
+    C: Clock,
+{
+    write(cache, |cache| {
+        let recompute = cache.clock().now().saturating_duration_since(started);
+        cache.put(key.clone(), value);
+        cache.record_recompute_time(&key, recompute);
+    })
+    .await;
+}
+
+/// Future loading a key, shared by the tasks awaiting its flight so that
+/// any of them may poll it, along with the wakers of those tasks.
+#[derive(Default)]
+struct Load {
+    state: Mutex<LoadState>,
+}
+
+#[derive(Default)]
+struct LoadState {
+    future: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+    wakers: HashMap<usize, Waker>,
+    waiters: usize,
+    next_id: usize,
+}
+
+impl Load {
+    /// Sets the future to be polled by the awaiting tasks, and wakes them up.
+    fn start<F>(&self, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.state
+            .lock()
+            .expect("failed to acquire load lock")
+            .future = Some(Box::pin(future));
+        self.wake_all();
+    }
+
+    /// Wakes up all tasks awaiting the future.
+    fn wake_all(&self) {
+        let wakers: Vec<_> = {
+            let mut state = self.state.lock().expect("failed to acquire load lock");
+            state.wakers.drain().map(|(_, waker)| waker).collect()
+        };
+
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+impl Waiters for Arc<Load> {
+    fn wake_all(&self) {
+        Load::wake_all(self);
+    }
+}
+
+/// Waker of a shared future, which wakes up all tasks awaiting it, since
+/// any of them may poll it next.
+struct Relay(Weak<Load>);
+
+impl Wake for Relay {
+    fn wake(self: Arc<Self>) {
+        if let Some(load) = self.0.upgrade() {
+            load.wake_all();
+        }
+    }
+}
+
+/// Future of a task awaiting a flight, which polls the shared future of
+/// the flight unless another task is doing so.
+struct Join<V> {
+    flight: Arc<Flight<V>>,
+    id: usize,
+}
+
+impl<V> Join<V> {
+    fn new(flight: Arc<Flight<V>>) -> Self {
+        let id = {
+            let mut state = flight
+                .waiters()
+                .state
+                .lock()
+                .expect("failed to acquire load lock");
+            state.waiters += 1;
+            state.next_id += 1;
+            state.next_id
+        };
+
+        Self { flight, id }
+    }
+}
+
+impl<V: Clone> Future for Join<V> {
+    type Output = Outcome<V>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let load = self.flight.waiters();
+        // Registering the waker before checking the outcome ensures that
+        // a flight landing in between wakes up the task.
+        let future = {
+            let mut state = load.state.lock().expect("failed to acquire load lock");
+            state.wakers.insert(self.id, cx.waker().clone());
+            match self.flight.outcome() {
+                Some(outcome) => return Poll::Ready(outcome),
+                None => state.future.take(),
+            }
+        };
+
+        // Without a future, another task is polling it, and wakes up this one
+        // if it remains pending.
+        if let Some(mut future) = future {
+            let waker = Waker::from(Arc::new(Relay(Arc::downgrade(load))));
+            if future
+                .as_mut()
+                .poll(&mut Context::from_waker(&waker))
+                .is_pending()
+            {
+                load.state
+                    .lock()
+                    .expect("failed to acquire load lock")
+                    .future = Some(future);
+            }
+        }
+
+        match self.flight.outcome() {
+            Some(outcome) => Poll::Ready(outcome),
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl<V> Drop for Join<V> {
+    /// Drops the shared future once the last task awaiting it goes away,
+    /// which abandons its flight.
+    fn drop(&mut self) {
+        let future = match self.flight.waiters().state.lock() {
+            Ok(mut state) => {
+                state.wakers.remove(&self.id);
+                state.waiters -= 1;
+                if state.waiters == 0 {
+                    state.future.take()
+                } else {
+                    None
+                }
+            }
+            Err(_) => None,
+        };
+
+        drop(future);
+    }
+}
+
+impl fmt::Debug for Load {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let waiters = self.state.lock().map_or(0, |state| state.waiters);
+        f.debug_struct("Load").field("waiters", &waiters).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    use tokio::sync::oneshot;
+    use tokio::time;
+
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn get_or_insert_with_coalesces_concurrent_loads() {
+        let cache = AsyncCache::<&str, u64>::default();
+        let loads = Arc::new(AtomicUsize::new(0));
+        let tasks: Vec<_> = (0..8)
+            .map(|_| {
+                let cache = cache.clone();
+                let loads = loads.clone();
+                tokio::spawn(async move {
+                    cache
+                        .get_or_insert_with("a", || async move {
+                            loads.fetch_add(1, Ordering::SeqCst);
+                            time::sleep(Duration::from_millis(200)).await;
+                            42
+                        })
+                        .await
+                })
+            })
+            .collect();
+
+        time::sleep(Duration::from_millis(50)).await;
+        cache.put("b", 1).await;
+        assert_eq!(cache.get("b").await, Some(1));
+
+        for task in tasks {
+            assert_eq!(task.await.unwrap(), 42);
+        }
+
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+        assert_eq!(cache.get("a").await, Some(42));
+    }
+
+    #[tokio::test]
+    async fn get_yields_while_the_cache_is_locked() {
+        let cache = AsyncCache::<&str, u64>::default();
+        let guard = cache.cache.write().await;
+        let get = {
+            let cache = cache.clone();
+            tokio::spawn(async move { cache.get("a").await })
+        };
+
+        // On a single-threaded runtime, a blocking lock would never let this task resume.
+        tokio::task::yield_now().await;
+        drop(guard);
+        assert_eq!(get.await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn get_or_try_insert_with_propagates_errors_without_caching() {
+        let cache = AsyncCache::<&str, u64>::default();
+        let (started, start) = oneshot::channel();
+        let leader = {
+            let cache = cache.clone();
+            tokio::spawn(async move {
+                cache
+                    .get_or_try_insert_with("a", || async {
+                        started.send(()).unwrap();
+                        time::sleep(Duration::from_millis(100)).await;
+                        Err::<u64, _>("failed")
+                    })
+                    .await
+            })
+        };
+
+        start.await.unwrap();
+        let follower = cache
+            .get_or_try_insert_with("a", || async { Ok::<_, &str>(42) })
+            .await;
+        assert_eq!(follower, Err("failed"));
+        assert_eq!(leader.await.unwrap(), Err("failed"));
+        assert_eq!(cache.get("a").await, None);
+
+        let retry = cache
+            .get_or_try_insert_with("a", || async { Ok::<_, &str>(42) })
+            .await;
+        assert_eq!(retry, Ok(42));
+    }
+
+    #[tokio::test]
+    async fn get_or_insert_with_keeps_loading_when_the_caller_is_cancelled() {
+        let cache = AsyncCache::<&str, u64>::default();
+        let loads = Arc::new(AtomicUsize::new(0));
+        let (started, start) = oneshot::channel();
+        let leader = {
+            let cache = cache.clone();
+            let loads = loads.clone();
+            tokio::spawn(async move {
+                cache
+                    .get_or_insert_with("a", || async move {
+                        loads.fetch_add(1, Ordering::SeqCst);
+                        started.send(()).unwrap();
+                        time::sleep(Duration::from_millis(100)).await;
+                        1
+                    })
+                    .await
+            })
+        };
+
+        start.await.unwrap();
+        let follower = {
+            let cache = cache.clone();
+            tokio::spawn(async move { cache.get_or_insert_with("a", || async { 2 }).await })
+        };
+
+        time::sleep(Duration::from_millis(20)).await;
+        leader.abort();
+        assert!(leader.await.unwrap_err().is_cancelled());
+        assert_eq!(follower.await.unwrap(), 1);
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+        assert_eq!(cache.get("a").await, Some(1));
+    }
+
+    #[tokio::test]
+    async fn get_or_insert_with_cancels_when_all_waiters_are_dropped() {
+        let cache = AsyncCache::<&str, u64>::default();
+        let dropped = Arc::new(AtomicBool::new(false));
+        let load = {
+            let dropped = dropped.clone();
+            cache.get_or_insert_with("a", || async move {
+                let _guard = DropFlag(dropped);
+                time::sleep(Duration::from_secs(3_600)).await;
+                1
+            })
+        };
+        let join = cache.get_or_insert_with("a", || async { 2 });
+
+        let timeout = time::timeout(Duration::from_millis(50), async {
+            tokio::join!(load, join)
+        });
+        assert!(timeout.await.is_err());
+        assert!(dropped.load(Ordering::SeqCst));
+        assert!(cache.flights.is_empty());
+        assert_eq!(cache.get("a").await, None);
+    }
+
+    struct DropFlag(Arc<AtomicBool>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+}
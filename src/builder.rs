@@ -0,0 +1,315 @@
+//! Provides a builder for configuring [Cache], [SyncCache], and [LoadingCache] instances.
+
+use std::collections::hash_map::RandomState;
+use std::time::Duration;
+
+use crate::clock::{Clock, SystemClock};
+use crate::expiry::Expiry;
+use crate::listener::{Notifier, RemovalListener};
+use crate::loading::{CacheLoader, LoadingCache};
+use crate::policy::{EvictionPolicy, Lru};
+use crate::weigher::{Weigher, Weighing};
+use crate::{Cache, SyncCache};
+
+/// Builder of [Cache], [SyncCache], and [LoadingCache] instances.
+///
+/// By default, the resulting cache is unbounded, evicts the least-recently-used
+/// items once bounded, uses the standard hasher and the system clock, and stores
+/// items without expiration unless an explicit expiration time is given.
+pub struct CacheBuilder<K, V, P = Lru, S = RandomState, C = SystemClock> {
+    pub(crate) max_entries: Option<usize>,
+    pub(crate) weighing: Option<Weighing<K, V>>,
+    pub(crate) policy: P,
+    pub(crate) hasher: S,
+    pub(crate) default_ttl: Option<Duration>,
+    pub(crate) time_to_idle: Option<Duration>,
+    pub(crate) stale_ttl: Option<Duration>,
+    pub(crate) early_expiration: Option<f64>,
+    pub(crate) jitter: Option<Jitter>,
+    pub(crate) seed: Option<u64>,
+    pub(crate) expiry: Option<Box<dyn Expiry<K, V> + Send + Sync>>,
+    pub(crate) clock: C,
+    pub(crate) timing_wheel: bool,
+    pub(crate) purge_max_entries: Option<usize>,
+    pub(crate) purge_max_time: Option<Duration>,
+    pub(crate) notifier: Option<Notifier<K, V>>,
+}
+
+/// Random amount by which the expiration times of stored items are moved back.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Jitter {
+    /// Up to the given duration.
+    Max(Duration),
+    /// Up to the given fraction of the time-to-live.
+    Ratio(f64),
+}
+
+impl<K, V> Default for CacheBuilder<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> CacheBuilder<K, V> {
+    /// Creates a builder with the default configuration.
+    pub fn new() -> Self {
+        Self {
+            max_entries: None,
+            weighing: None,
+            policy: Lru::default(),
+            hasher: RandomState::new(),
+            default_ttl: None,
+            time_to_idle: None,
+            stale_ttl: None,
+            early_expiration: None,
+            jitter: None,
+            seed: None,
+            expiry: None,
+            clock: SystemClock,
+            timing_wheel: false,
+            purge_max_entries: None,
+            purge_max_time: None,
+            notifier: None,
+        }
+    }
+}
+
+impl<K, V, P, S, C> CacheBuilder<K, V, P, S, C> {
+    /// Bounds the cache to hold at most `max_entries` items.
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = Some(max_entries);
+        self
+    }
+
+    /// Bounds the cache to hold items up to a total weight of `max_weight`,
+    /// as computed by the given weigher.
+    pub fn weigher<W>(mut self, max_weight: u64, weigher: W) -> Self
+    where
+        W: Weigher<K, V> + Send + Sync + 'static,
+    {
+        self.weighing = Some(Weighing::new(max_weight, weigher));
+        self
+    }
+
+    /// Sets whether storing an item heavier than the maximum weight is rejected
+    /// (the default), or admitted by evicting all other items.
+    /// Has no effect unless a weigher is configured.
+    pub fn reject_oversized(mut self, reject: bool) -> Self {
+        if let Some(weighing) = &mut self.weighing {
+            weighing.reject_oversized = reject;
+        }
+
+        self
+    }
+
+    /// Sets the time-to-live of items stored without an explicit expiration time.
+    pub fn default_ttl(mut self, ttl: Duration) -> Self {
+        self.default_ttl = Some(ttl);
+        self
+    }
+
+    /// Expires items that have not been retrieved for the given duration.
+    /// Each retrieval of an item thus extends its lifetime, up to its expiration time
+    /// (if any), which acts as an absolute maximum lifetime; see [CacheBuilder::default_ttl].
+    pub fn time_to_idle(mut self, time_to_idle: Duration) -> Self {
+        self.time_to_idle = Some(time_to_idle);
+        self
+    }
+
+    /// Keeps items for the given duration once they expire, during which they are stale:
+    /// no longer returned by [Cache::get], but still returned by [Cache::get_tagged],
+    /// and by a [LoadingCache] whose loader fails, or while it refreshes them.
+    /// Expired items are only purged once that duration has passed.
+    pub fn stale_ttl(mut self, stale_ttl: Duration) -> Self {
+        self.stale_ttl = Some(stale_ttl);
+        self
+    }
+
+    /// Signals that items should be recomputed ahead of their expiration, with a probability
+    /// that grows as they approach it, and with their recorded recompute time; see
+    /// [Cache::get_early]. Larger values of `beta` favor earlier recomputation;
+    /// 1.0 is a sensible default.
+    pub fn early_expiration(mut self, beta: f64) -> Self {
+        self.early_expiration = Some(beta);
+        self
+    }
+
+    /// Moves the expiration time of each stored item back by a random duration of up to
+    /// `max_jitter`, but not before the time of storage, so that items stored with
+    /// the same expiration time expire at different times. Replaces any jitter
+    /// configured using [CacheBuilder::ttl_jitter_percent].
+    pub fn ttl_jitter(mut self, max_jitter: Duration) -> Self {
+        self.jitter = Some(Jitter::Max(max_jitter));
+        self
+    }
+
+    /// Moves the expiration time of each stored item back by a random duration
+    /// of up to the given percentage of its time-to-live, so that items stored with
+    /// the same expiration time expire at different times. Replaces any jitter
+    /// configured using [CacheBuilder::ttl_jitter]. Percentages above 100 are capped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `percent` is negative or not finite.
+    pub fn ttl_jitter_percent(mut self, percent: f64) -> Self {
+        assert!(
+            percent.is_finite() && percent >= 0.0,
+            "jitter percentage must be finite and non-negative, got {}",
+            percent
+        );
+        self.jitter = Some(Jitter::Ratio(percent / 100.0));
+        self
+    }
+
+    /// Seeds the random number generator used for [CacheBuilder::ttl_jitter] and
+    /// [CacheBuilder::early_expiration], so that, along with a manual clock,
+    /// the resulting cache behaves deterministically.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Computes the time-to-live of items stored without an explicit expiration time
+    /// using the given expiry policy, which may also adjust it upon retrieval.
+    /// Takes precedence over [CacheBuilder::default_ttl] and [CacheBuilder::time_to_idle].
+    pub fn expiry<E>(mut self, expiry: E) -> Self
+    where
+        E: Expiry<K, V> + Send + Sync + 'static,
+    {
+        self.expiry = Some(Box::new(expiry));
+        self
+    }
+
+    /// Sets whether expiration times are tracked using a hierarchical timing wheel,
+    /// rather than in order (the default). The timing wheel tracks items in constant
+    /// time, but purges expired items with a resolution of about a millisecond.
+    pub fn timing_wheel(mut self, enabled: bool) -> Self {
+        self.timing_wheel = enabled;
+        self
+    }
+
+    /// Limits the number of tracked expirations processed by each insertion,
+    /// leaving the remaining expired items to subsequent insertions or to
+    /// [Cache::run_pending_tasks]. By default, each insertion removes all expired items.
+    pub fn purge_max_entries(mut self, max_entries: usize) -> Self {
+        self.purge_max_entries = Some(max_entries);
+        self
+    }
+
+    /// Limits the time spent removing expired items during each insertion,
+    /// leaving the remaining expired items to subsequent insertions or to
+    /// [Cache::run_pending_tasks]. By default, each insertion removes all expired items,
+    /// as it does given a duration too long to be represented as a point in time.
+    pub fn purge_max_time(mut self, max_time: Duration) -> Self {
+        self.purge_max_time = Some(max_time);
+        self
+    }
+
+    /// Notifies the given listener of each entry removed from the cache, along with
+    /// the cause of its removal. A [SyncCache] notifies the listener after releasing
+    /// its lock, so the listener may access the cache.
+    pub fn removal_listener<L>(mut self, listener: L) -> Self
+    where
+        L: RemovalListener<K, V> + Send + Sync + 'static,
+    {
+        self.notifier = Some(Notifier::new(listener));
+        self
+    }
+
+    /// Sets the policy that selects items to evict once the cache is full.
+    pub fn policy<Q: EvictionPolicy>(self, policy: Q) -> CacheBuilder<K, V, Q, S, C> {
+        CacheBuilder {
+            max_entries: self.max_entries,
+            weighing: self.weighing,
+            policy,
+            hasher: self.hasher,
+            default_ttl: self.default_ttl,
+            time_to_idle: self.time_to_idle,
+            stale_ttl: self.stale_ttl,
+            early_expiration: self.early_expiration,
+            jitter: self.jitter,
+            seed: self.seed,
+            expiry: self.expiry,
+            clock: self.clock,
+            timing_wheel: self.timing_wheel,
+            purge_max_entries: self.purge_max_entries,
+            purge_max_time: self.purge_max_time,
+            notifier: self.notifier,
+        }
+    }
+
+    /// Sets the hasher used to hash the cache's keys.
+    pub fn hasher<T>(self, hasher: T) -> CacheBuilder<K, V, P, T, C> {
+        CacheBuilder {
+            max_entries: self.max_entries,
+            weighing: self.weighing,
+            policy: self.policy,
+            hasher,
+            default_ttl: self.default_ttl,
+            time_to_idle: self.time_to_idle,
+            stale_ttl: self.stale_ttl,
+            early_expiration: self.early_expiration,
+            jitter: self.jitter,
+            seed: self.seed,
+            expiry: self.expiry,
+            clock: self.clock,
+            timing_wheel: self.timing_wheel,
+            purge_max_entries: self.purge_max_entries,
+            purge_max_time: self.purge_max_time,
+            notifier: self.notifier,
+        }
+    }
+
+    /// Sets the clock used to determine whether items have expired.
+    pub fn clock<D: Clock>(self, clock: D) -> CacheBuilder<K, V, P, S, D> {
+        CacheBuilder {
+            max_entries: self.max_entries,
+            weighing: self.weighing,
+            policy: self.policy,
+            hasher: self.hasher,
+            default_ttl: self.default_ttl,
+            time_to_idle: self.time_to_idle,
+            stale_ttl: self.stale_ttl,
+            early_expiration: self.early_expiration,
+            jitter: self.jitter,
+            seed: self.seed,
+            expiry: self.expiry,
+            clock,
+            timing_wheel: self.timing_wheel,
+            purge_max_entries: self.purge_max_entries,
+            purge_max_time: self.purge_max_time,
+            notifier: self.notifier,
+        }
+    }
+}
+
+impl<K, V, P: EvictionPolicy, S, C: Clock> CacheBuilder<K, V, P, S, C> {
+    /// Builds a [Cache] with this configuration.
+    pub fn build(mut self) -> Cache<K, V, P, S, C> {
+        if let Some(max_entries) = self.max_entries {
+            self.policy.set_capacity(max_entries);
+        }
+
+        Cache::from_builder(self)
+    }
+
+    /// Builds a [SyncCache] with this configuration.
+    pub fn build_sync(self) -> SyncCache<K, V, P, S, C> {
+        self.build().into()
+    }
+
+    /// Builds a [LoadingCache] with this configuration, loading missing values
+    /// using the given loader.
+    pub fn build_loading<L>(self, loader: L) -> LoadingCache<K, V, L, P, S, C>
+    where
+        L: CacheLoader<K, V>,
+    {
+        LoadingCache::new(self.build_sync(), loader)
+    }
+
+    /// Builds an [AsyncCache](crate::AsyncCache) with this configuration.
+    #[cfg(feature = "tokio")]
+    pub fn build_async(self) -> crate::AsyncCache<K, V, P, S, C> {
+        self.build().into()
+    }
+}
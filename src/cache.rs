@@ -1,12 +1,24 @@
 //! Simple key/value cache implementation.
 
-#[cfg(test)]
-use mock_instant::Instant;
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::collections::BTreeSet;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
-use std::collections::{BTreeSet, HashMap};
-use std::hash::Hash;
-#[cfg(not(test))]
-use std::time::Instant;
+use crate::builder::{CacheBuilder, Jitter};
+use crate::clock::{Clock, SystemClock};
+use crate::entry::{OccupiedEntry, Op, VacantEntry};
+use crate::expiry::Expiry;
+use crate::index::Index;
+use crate::listener::{Notifier, RemovalCause, Removals};
+use crate::policy::{EvictionPolicy, Lru};
+use crate::rng::Rng;
+use crate::slab::Slab;
+use crate::weigher::{Weigher, Weighing};
+use crate::wheel::TimingWheel;
 
 /// Simple key/value cache that supports optional item expiration.
 ///
@@ -16,168 +28,2382 @@ use std::time::Instant;
 ///
 /// Thus, the cost of cleaning up expired items is incurred during insertion.
 /// The memory required to track expiring items is proportional to the number
-/// of items in cache.
+/// of items in cache. To bound the latency of insertion, a cache configured with
+/// [CacheBuilder::purge_max_entries] or [CacheBuilder::purge_max_time] leaves
+/// expired items beyond that budget to subsequent insertions or to
+/// [Cache::run_pending_tasks].
+///
+/// By default, expiring items are tracked in order of their expiration times, so
+/// tracking an item costs O(log n). A cache configured with [CacheBuilder::timing_wheel]
+/// instead tracks them using a hierarchical timing wheel, which costs O(1) but purges
+/// expired items with a resolution of about a millisecond.
+///
+/// Items stored with [Cache::put] expire after the cache's default time-to-live,
+/// if one was configured using [CacheBuilder::default_ttl].
+///
+/// A cache configured with [CacheBuilder::time_to_idle] also expires items that
+/// have not been retrieved for the given duration. Since retrieval only records
+/// the time of access, the tracked expiration time of such an item is moved forward
+/// when the cleanup reaches it, rather than upon each access.
+///
+/// Alternatively, a cache configured with [CacheBuilder::expiry] computes the
+/// time-to-live of each item stored with [Cache::put] from the item itself, and
+/// may adjust it upon retrieval. Likewise, an item whose expiration time is changed
+/// upon retrieval is rescheduled once the cleanup reaches its tracked expiration time;
+/// until then, it is merely no longer returned if its new expiration time has passed.
+///
+/// A cache configured with [CacheBuilder::ttl_jitter] or [CacheBuilder::ttl_jitter_percent]
+/// moves the expiration time of each stored item back by a random amount, so that items
+/// stored with the same expiration time do not all expire, and get purged, at once.
+///
+/// A cache configured with [CacheBuilder::stale_ttl] keeps expired items for the given
+/// duration, during which they are stale: [Cache::get] no longer returns them, but
+/// [Cache::get_tagged] does, and they are only purged once that duration has passed.
+///
+/// A cache configured with [CacheBuilder::early_expiration] may signal that an item
+/// should be recomputed before it expires, as returned by [Cache::get_early]. The closer
+/// the item is to its expiration time, and the longer it took to compute as recorded using
+/// [Cache::record_recompute_time], the more likely the signal; thus, recomputations
+/// of items expiring at the same time are spread out (see the XFetch algorithm in
+/// Vattani et al., *Optimal Probabilistic Cache Stampede Prevention*, VLDB 2015).
+///
+/// *Capacity*
+/// A cache created with [Cache::with_capacity] or [Cache::with_policy] holds
+/// a bounded number of items, while one created with [Cache::with_weigher] holds
+/// items up to a maximum total weight; both bounds may also be combined using [CacheBuilder].
+/// When an insertion would exceed the bound, expired items are removed first;
+/// if that is not enough, items selected by the cache's [EvictionPolicy] are evicted
+/// (by default, the least-recently-used ones).
 ///
 /// *Retrieval*
 /// When an item with expiration is retrieved, its expiration time is checked
-/// against the current time. The cached value is only returned if it hasn't
-/// expired yet. However, no other maintenance is performed.
+/// against the current time, as reported by the cache's [Clock]. The cached value
+/// is only returned if it hasn't expired yet. However, no other maintenance is
+/// performed, except for recording the access in a bounded or idle-expiring cache.
 ///
 /// Thus, item retrieval should be constant for a given cache size.
-#[derive(Debug, Default)]
-pub struct Cache<K, V> {
-    map: HashMap<K, CachedValue<V>>,
-    expirations: BTreeSet<Expiration<K>>,
+///
+/// *Removal*
+/// A cache configured with [CacheBuilder::removal_listener] notifies the listener
+/// of each item it removes, along with the [RemovalCause], before the method
+/// that removed the item returns.
+#[derive(Debug)]
+pub struct Cache<K, V, P = Lru, S = RandomState, C = SystemClock> {
+    entries: Slab<Entry<K, V>>,
+    index: Index,
+    hasher: S,
+    expirations: Expirations,
+    capacity: Option<usize>,
+    weighing: Option<Weighing<K, V>>,
+    policy_capacity: usize,
+    policy: Mutex<P>,
+    default_ttl: Option<Duration>,
+    time_to_idle: Option<Duration>,
+    stale_ttl: Duration,
+    early_expiration: Option<f64>,
+    jitter: Option<Jitter>,
+    rng: Rng,
+    expiry: Option<Box<dyn Expiry<K, V> + Send + Sync>>,
+    epoch: Epoch,
+    clock: C,
+    purge_max_entries: Option<usize>,
+    purge_max_time: Option<Duration>,
+    notifier: Option<Notifier<K, V>>,
+    defer_notifications: bool,
+    writes: u64,
 }
 
+/// Cached item, stored in a slot whose index identifies the item
+/// to the cache's eviction policy and expiration tracking.
 #[derive(Debug)]
-struct CachedValue<V> {
+struct Entry<K, V> {
+    key: K,
     value: V,
+    hash: u64,
     expires: Option<Instant>,
+    tracked: Option<Instant>,
+    deadline: AtomicU64,
+    implicit: bool,
+    grace: Duration,
+    recompute: Duration,
+    written: u64,
+    version: u64,
+    refresh_failures: u32,
+    weight: u64,
+}
+
+/// Freshness of a cached value, as returned by [Cache::get_tagged].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Freshness {
+    /// The value has not expired.
+    Fresh,
+    /// The value has expired, but is kept for its stale time-to-live;
+    /// see [CacheBuilder::stale_ttl].
+    Stale,
+}
+
+/// Number of tracked expirations processed between checks of the purge time budget.
+const PURGE_BATCH: usize = 64;
+
+/// Number of expired items removed per chunk by the reaper of a cache without purge budget.
+const PURGE_CHUNK: usize = 1024;
+
+/// Stamp of an entry that never expires.
+const NEVER: u64 = u64::MAX;
+
+/// Reference point of the expiration times of a cache's entries. Expiration times
+/// may change upon retrieval, so they are stored as nanoseconds elapsed since
+/// the cache's creation, which can be updated atomically.
+#[derive(Clone, Copy, Debug)]
+struct Epoch(Instant);
+
+impl Epoch {
+    fn stamp(self, deadline: Option<Instant>) -> u64 {
+        deadline.map_or(NEVER, |deadline| {
+            let nanos = deadline.saturating_duration_since(self.0).as_nanos();
+            u64::try_from(nanos).unwrap_or(NEVER).min(NEVER - 1)
+        })
+    }
+
+    fn expired(self, stamp: u64, now: Instant) -> bool {
+        matches!(self.instant(stamp), Some(deadline) if deadline <= now)
+    }
+
+    fn instant(self, stamp: u64) -> Option<Instant> {
+        if stamp == NEVER {
+            None
+        } else {
+            Some(self.0 + Duration::from_nanos(stamp))
+        }
+    }
+}
+
+impl<K, V> Default for Cache<K, V> {
+    fn default() -> Self {
+        CacheBuilder::new().build()
+    }
+}
+
+impl<K, V> Cache<K, V> {
+    /// Creates a cache that holds at most `max_entries` items.
+    /// Once full, storing a new item evicts the least-recently-used one.
+    pub fn with_capacity(max_entries: usize) -> Self {
+        CacheBuilder::new().max_entries(max_entries).build()
+    }
+
+    /// Creates a cache that holds items up to a total weight of `max_weight`,
+    /// as computed by the given weigher. Once full, storing a new item evicts
+    /// the least-recently-used items until the total weight is within bounds.
+    ///
+    /// By default, items heavier than `max_weight` are rejected;
+    /// see [Cache::set_reject_oversized].
+    pub fn with_weigher<W>(max_weight: u64, weigher: W) -> Self
+    where
+        W: Weigher<K, V> + Send + Sync + 'static,
+    {
+        CacheBuilder::new().weigher(max_weight, weigher).build()
+    }
+}
+
+impl<K, V, P: EvictionPolicy> Cache<K, V, P> {
+    /// Creates a cache that holds at most `max_entries` items.
+    /// Once full, storing a new item evicts the one selected by the given policy.
+    pub fn with_policy(max_entries: usize, policy: P) -> Self {
+        CacheBuilder::new()
+            .max_entries(max_entries)
+            .policy(policy)
+            .build()
+    }
+}
+
+impl<K, V, P, S, C> Cache<K, V, P, S, C> {
+    pub(crate) fn from_builder(builder: CacheBuilder<K, V, P, S, C>) -> Self
+    where
+        C: Clock,
+    {
+        Self {
+            entries: Slab::default(),
+            index: Index::default(),
+            hasher: builder.hasher,
+            expirations: if builder.timing_wheel {
+                Expirations::Wheel(TimingWheel::default())
+            } else {
+                Expirations::Ordered(BTreeSet::new())
+            },
+            capacity: builder.max_entries,
+            weighing: builder.weighing,
+            policy_capacity: builder.max_entries.unwrap_or_default(),
+            policy: Mutex::new(builder.policy),
+            default_ttl: builder.default_ttl,
+            time_to_idle: builder.time_to_idle.filter(|_| builder.expiry.is_none()),
+            stale_ttl: builder.stale_ttl.unwrap_or_default(),
+            early_expiration: builder.early_expiration,
+            jitter: builder.jitter,
+            rng: builder.seed.map_or_else(Rng::from_entropy, Rng::new),
+            expiry: builder.expiry,
+            epoch: Epoch(builder.clock.now()),
+            clock: builder.clock,
+            purge_max_entries: builder.purge_max_entries,
+            purge_max_time: builder.purge_max_time,
+            notifier: builder.notifier,
+            defer_notifications: false,
+            writes: 0,
+        }
+    }
+
+    /// Returns the number of cached items, including expired items that
+    /// have not been removed yet; see [Cache::len_unexpired].
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the cache holds no items, including expired items that
+    /// have not been removed yet; see [Cache::is_empty_unexpired].
+    pub fn is_empty(&self) -> bool {
+        self.entries.len() == 0
+    }
+
+    /// Returns the time at which the earliest tracked expiration is due, if any.
+    /// For a cache configured with [CacheBuilder::timing_wheel], the returned time may
+    /// precede the actual expiration time, but never follows it.
+    ///
+    /// The tracked expiration time of an item whose expiration time changed upon retrieval
+    /// is only updated when it is reached, at which point the item is rescheduled.
+    pub fn next_expiration(&self) -> Option<Instant> {
+        match &self.expirations {
+            Expirations::Ordered(expirations) => expirations.iter().next().map(|item| item.expires),
+            Expirations::Wheel(wheel) => wheel
+                .next_expiration()
+                .and_then(|stamp| self.epoch.instant(stamp)),
+        }
+    }
+
+    /// Returns the total weight of the cached items, as computed by the cache's weigher.
+    /// Always zero if the cache was not created with a weigher.
+    pub fn weight(&self) -> u64 {
+        self.weighing
+            .as_ref()
+            .map_or(0, |weighing| weighing.total_weight)
+    }
+
+    /// Sets whether storing an item heavier than the cache's maximum weight is rejected,
+    /// which also removes any value previously cached for the same key (the default),
+    /// or admitted by evicting all other items. Has no effect if the cache was not
+    /// created with a weigher.
+    pub fn set_reject_oversized(&mut self, reject: bool) {
+        if let Some(weighing) = &mut self.weighing {
+            weighing.reject_oversized = reject;
+        }
+    }
+
+    /// Returns the time at which an entry with the given expiration time,
+    /// accessed at the given time, expires.
+    fn deadline(&self, expires: Option<Instant>, now: Instant) -> Option<Instant> {
+        match self
+            .time_to_idle
+            .and_then(|time_to_idle| now.checked_add(time_to_idle))
+        {
+            Some(idle_deadline) => {
+                Some(expires.map_or(idle_deadline, |expires| expires.min(idle_deadline)))
+            }
+            None => expires,
+        }
+    }
+
+    /// Moves the given expiration time, if any, back by a random amount
+    /// within the configured jitter, but not before the given time.
+    fn jittered(&self, expires: Option<Instant>, now: Instant) -> Option<Instant> {
+        let (expires, jitter) = match (expires, self.jitter) {
+            (Some(expires), Some(jitter)) => (expires, jitter),
+            _ => return expires,
+        };
+
+        let remaining = expires.saturating_duration_since(now);
+        let max = match jitter {
+            Jitter::Max(max) => max.min(remaining),
+            Jitter::Ratio(ratio) => remaining.mul_f64(ratio.clamp(0.0, 1.0)),
+        };
+
+        Some(expires - max.mul_f64(self.rng.next_f64()))
+    }
+
+    pub(crate) fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    /// Holds on to removals until they are taken using [Cache::take_removals],
+    /// rather than notifying the listener right away.
+    pub(crate) fn defer_notifications(&mut self) {
+        self.defer_notifications = true;
+    }
+
+    /// Takes the removals the listener, if any, has not been notified of yet.
+    pub(crate) fn take_removals(&mut self) -> Removals<K, V> {
+        self.notifier
+            .as_mut()
+            .map_or_else(Removals::default, Notifier::take)
+    }
+
+    /// Notifies the listener, if any, of pending removals, unless deferred.
+    fn notify_removals(&mut self) {
+        if !self.defer_notifications {
+            self.take_removals().notify();
+        }
+    }
+
+    fn is_bounded(&self) -> bool {
+        self.capacity.is_some() || self.weighing.is_some()
+    }
+
+    fn exceeds_bounds(&self) -> bool {
+        let len = self.entries.len();
+        self.capacity.map_or(false, |capacity| len > capacity)
+            || self.weighing.as_ref().map_or(false, |weighing| {
+                weighing.total_weight > weighing.max_weight && len > 1
+            })
+    }
 }
 
-impl<K: Clone + Eq + Hash + Ord, V> Cache<K, V> {
+impl<K, V, P, S, C> Cache<K, V, P, S, C>
+where
+    K: Eq + Hash,
+    P: EvictionPolicy,
+    S: BuildHasher,
+    C: Clock,
+{
     /// Stores a value for the given key, potentially replacing a previously cached value.
-    /// The entry never expires.
+    /// The entry expires after the time-to-live computed by the cache's [Expiry], if any,
+    /// or else after its default time-to-live, if any; otherwise, it never expires.
     pub fn put(&mut self, key: K, value: V) {
-        self.put_exp(key, value, None);
+        let hash = hash_key(&self.hasher, &key);
+        let found = self.find(hash, &key);
+        self.put_at(hash, found, key, value);
+    }
+
+    /// Stores a value for the given key, expiring after the given time-to-live.
+    /// A time-to-live too long to be represented never expires.
+    pub fn put_ttl(&mut self, key: K, value: V, ttl: Duration) {
+        self.put_exp(key, value, self.clock.now().checked_add(ttl));
     }
 
     /// Stores a value for the given key, with an optional expiration time.
+    /// The cache's [Expiry], if any, is not consulted.
     pub fn put_exp(&mut self, key: K, value: V, expires: Option<Instant>) {
-        if let Some(old_cached) = self.map.insert(key.clone(), CachedValue { value, expires }) {
-            if let Some(expires) = old_cached.expires {
-                self.expirations.remove(&Expiration {
-                    key: key.clone(),
-                    expires,
-                });
+        let hash = hash_key(&self.hasher, &key);
+        let found = self.find(hash, &key);
+        self.insert_at(hash, found, key, value, expires);
+    }
+
+    /// Stores a value for the given key, which is fresh until `fresh_until`, if given,
+    /// and then kept while stale until `stale_until`, if given, or else indefinitely.
+    /// The cache's [Expiry], if any, and its stale time-to-live are not consulted.
+    pub fn put_stale(
+        &mut self,
+        key: K,
+        value: V,
+        fresh_until: Option<Instant>,
+        stale_until: Option<Instant>,
+    ) {
+        let grace = match (fresh_until, stale_until) {
+            (Some(fresh_until), Some(stale_until)) => {
+                stale_until.saturating_duration_since(fresh_until)
             }
+            (_, None) => Duration::MAX,
+            (None, Some(_)) => Duration::ZERO,
+        };
+
+        let hash = hash_key(&self.hasher, &key);
+        let found = self.find(hash, &key);
+        self.store(hash, found, key, value, fresh_until, grace);
+    }
+
+    /// Returns the cached value for the given key, if present and not expired.
+    ///
+    /// The key may be any borrowed form of the cache's key type,
+    /// but [Hash] and [Eq] on the borrowed form must match those for the key type.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.lookup(key).map(|slot| &self.entries[slot].value)
+    }
+
+    /// Returns the cached value for the given key, if present and either fresh or stale,
+    /// along with its freshness; see [CacheBuilder::stale_ttl].
+    /// Retrieving a stale value counts as an access, but does not extend its lifetime.
+    pub fn get_tagged<Q>(&self, key: &Q) -> Option<(&V, Freshness)>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.lookup_tagged(key)
+            .map(|(slot, freshness)| (&self.entries[slot].value, freshness))
+    }
+
+    /// Returns the cached value for the given key, if present and not expired,
+    /// along with whether it should be recomputed ahead of its expiration;
+    /// see [CacheBuilder::early_expiration].
+    pub fn get_early<Q>(&self, key: &Q) -> Option<(&V, bool)>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.lookup(key)
+            .map(|slot| (&self.entries[slot].value, self.expires_early(slot)))
+    }
+
+    /// Records the time it took to compute the value cached for the given key, if present,
+    /// which informs the decision to recompute it early; see [Cache::get_early].
+    /// The recorded time is kept when the value is replaced.
+    pub fn record_recompute_time<Q>(&mut self, key: &Q, recompute: Duration)
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        if let Some(slot) = self.find(hash_key(&self.hasher, key), key) {
+            self.entries[slot].recompute = recompute;
         }
+    }
 
-        if let Some(expires) = expires {
-            self.expirations.insert(Expiration { key, expires });
+    /// Returns the cached value for the given key, if present and either fresh or stale,
+    /// along with the time elapsed since it was stored, its freshness, and whether
+    /// it should be recomputed early.
+    pub(crate) fn get_aged<Q>(&self, key: &Q) -> Option<(&V, Duration, Freshness, bool)>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let (slot, freshness) = self.lookup_tagged(key)?;
+        let entry = &self.entries[slot];
+        let written = self.epoch.instant(entry.written).unwrap_or(self.epoch.0);
+        let age = self.clock.now().saturating_duration_since(written);
+        let early = freshness == Freshness::Fresh && self.expires_early(slot);
+        Some((&entry.value, age, freshness, early))
+    }
+
+    /// Decides whether the unexpired entry in the given slot should be recomputed early,
+    /// which is the case if its recompute time, scaled by the configured factor and
+    /// a random draw from an exponential distribution, reaches past its expiration time.
+    fn expires_early(&self, slot: usize) -> bool {
+        let beta = match self.early_expiration {
+            Some(beta) => beta,
+            None => return false,
+        };
+
+        let entry = &self.entries[slot];
+        if entry.recompute == Duration::ZERO {
+            return false;
         }
 
-        let now = Instant::now();
-        let expired: Vec<_> = self
-            .expirations
-            .iter()
-            .take_while(|&item| item.expires <= now)
-            .cloned()
-            .collect();
+        match self.epoch.instant(entry.deadline.load(Ordering::Relaxed)) {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(self.clock.now());
+                let gap = entry.recompute.as_secs_f64() * beta * -self.rng.next_f64().ln();
+                gap >= remaining.as_secs_f64()
+            }
+            None => false,
+        }
+    }
 
-        for item in expired {
-            self.map.remove(&item.key);
-            self.expirations.remove(&item);
+    /// Returns the version of the value cached for the given key, if present and either
+    /// fresh or stale, which changes whenever the value is replaced or modified.
+    /// Unlike [Cache::get_tagged], this does not count as an access to the value.
+    pub(crate) fn version_of<Q>(&self, key: &Q) -> Option<u64>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let slot = self.find(hash_key(&self.hasher, key), key)?;
+        let entry = &self.entries[slot];
+        let deadline = self.epoch.instant(entry.deadline.load(Ordering::Relaxed));
+        match stale_deadline(deadline, entry.grace) {
+            Some(stale_until) if stale_until <= self.clock.now() => None,
+            _ => Some(entry.version),
         }
     }
 
-    /// Returns the cached value for the given key, if present and not expired.
-    pub fn get(&self, key: &K) -> Option<&V> {
-        self.map.get(key).and_then(|cached| {
-            if let Some(expires) = cached.expires {
-                let now = Instant::now();
-                if expires <= now {
-                    return None;
-                }
+    /// Records a failed refresh of the given version of the value cached for the given key,
+    /// unless it was replaced in the meantime, and removes the value as expired once
+    /// its refresh has failed the given number of times in a row.
+    pub(crate) fn record_refresh_failure<Q>(&mut self, key: &Q, version: u64, max_failures: u32)
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let slot = match self.find(hash_key(&self.hasher, key), key) {
+            Some(slot) => slot,
+            None => return,
+        };
+
+        let entry = &mut self.entries[slot];
+        if entry.version != version {
+            return;
+        }
+
+        entry.refresh_failures += 1;
+        if entry.refresh_failures >= max_failures {
+            self.remove_at(slot, RemovalCause::Expired);
+        }
+    }
+
+    /// Returns the version of the next value to be stored or modified.
+    fn next_version(&mut self) -> u64 {
+        self.writes += 1;
+        self.writes
+    }
+
+    /// Returns the slot of the entry holding the given key, if present and not expired,
+    /// and records the access to it.
+    fn lookup<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let slot = self.find(hash_key(&self.hasher, key), key)?;
+        Some(slot).filter(|&slot| self.access(slot))
+    }
+
+    /// Returns the slot of the entry holding the given key, if present and either fresh
+    /// or stale, along with its freshness, and records the access to it.
+    fn lookup_tagged<Q>(&self, key: &Q) -> Option<(usize, Freshness)>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let slot = self.find(hash_key(&self.hasher, key), key)?;
+        if self.access(slot) {
+            return Some((slot, Freshness::Fresh));
+        }
+
+        let entry = &self.entries[slot];
+        let deadline = self.epoch.instant(entry.deadline.load(Ordering::Relaxed));
+        let stale_until = stale_deadline(deadline, entry.grace);
+        if matches!(stale_until, Some(stale_until) if stale_until <= self.clock.now()) {
+            return None;
+        }
+
+        if self.is_bounded() {
+            self.policy
+                .lock()
+                .expect("failed to acquire policy lock")
+                .touch(slot);
+        }
+
+        Some((slot, Freshness::Stale))
+    }
+
+    /// Records an access to the entry in the given slot, unless it has expired,
+    /// and returns whether it has not.
+    fn access(&self, slot: usize) -> bool {
+        let entry = &self.entries[slot];
+        let deadline = self.epoch.instant(entry.deadline.load(Ordering::Relaxed));
+        if deadline.is_some() || self.expiry.is_some() {
+            let now = self.clock.now();
+            if matches!(deadline, Some(deadline) if deadline <= now) {
+                return false;
             }
 
-            Some(&cached.value)
-        })
+            if let (true, Some(expiry)) = (entry.implicit, &self.expiry) {
+                let remaining = deadline.map(|deadline| deadline.duration_since(now));
+                let deadline = expiry
+                    .expire_after_read(&entry.key, &entry.value, remaining)
+                    .and_then(|ttl| now.checked_add(ttl));
+                entry
+                    .deadline
+                    .store(self.epoch.stamp(deadline), Ordering::Relaxed);
+            } else if self.time_to_idle.is_some() {
+                let deadline = self.deadline(entry.expires, now);
+                entry
+                    .deadline
+                    .fetch_max(self.epoch.stamp(deadline), Ordering::Relaxed);
+            }
+        }
+
+        if self.is_bounded() {
+            self.policy
+                .lock()
+                .expect("failed to acquire policy lock")
+                .touch(slot);
+        }
+
+        true
+    }
+
+    /// Returns whether a value is cached for the given key and not expired.
+    /// Unlike [Cache::get], this does not count as an access to the value.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.find(hash_key(&self.hasher, key), key)
+            .map_or(false, |slot| {
+                !self.is_expired(&self.entries[slot], self.clock.now())
+            })
     }
 
     /// Deletes any cached value for the given key.
-    pub fn delete(&mut self, key: &K) {
-        if let Some(old_cached) = self.map.remove(key) {
-            if let Some(expires) = old_cached.expires {
-                self.expirations.remove(&Expiration {
-                    key: key.clone(),
+    pub fn delete<Q>(&mut self, key: &Q)
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        if let Some(slot) = self.find(hash_key(&self.hasher, key), key) {
+            self.remove_at(slot, RemovalCause::Explicit);
+        }
+    }
+
+    /// Returns the entry for the given key, in order to inspect or update it in place,
+    /// hashing the key only once. An item that has expired, but has not been
+    /// removed yet, is treated as vacant.
+    ///
+    /// Looking up the entry does not count as an access to its value.
+    pub fn entry(&mut self, key: K) -> crate::entry::Entry<'_, K, V, P, S, C> {
+        let hash = hash_key(&self.hasher, &key);
+        let found = self.find(hash, &key);
+        match found {
+            Some(slot) if !self.is_expired(&self.entries[slot], self.clock.now()) => {
+                crate::entry::Entry::Occupied(OccupiedEntry::new(self, key, hash, slot))
+            }
+            _ => crate::entry::Entry::Vacant(VacantEntry::new(self, key, hash, found)),
+        }
+    }
+
+    /// Updates the entry for the given key by performing the operation returned by
+    /// the given function, which is passed the current value, if present and not expired.
+    /// Returns the resulting value, if any.
+    pub fn compute<F>(&mut self, key: K, compute: F) -> Option<&V>
+    where
+        F: FnOnce(Option<&V>) -> Op<V>,
+    {
+        let now = self.clock.now();
+        match self.entry(key) {
+            crate::entry::Entry::Occupied(entry) => match compute(Some(entry.get())) {
+                Op::Nop => Some(entry.into_ref()),
+                Op::Put(value) => entry.insert(value),
+                Op::PutTtl(value, ttl) => entry.insert_exp(value, now.checked_add(ttl)),
+                Op::PutExp(value, expires) => entry.insert_exp(value, expires),
+                Op::Remove => {
+                    entry.remove();
+                    None
+                }
+            },
+            crate::entry::Entry::Vacant(entry) => match compute(None) {
+                Op::Nop | Op::Remove => None,
+                Op::Put(value) => entry.insert(value),
+                Op::PutTtl(value, ttl) => entry.insert_exp(value, now.checked_add(ttl)),
+                Op::PutExp(value, expires) => entry.insert_exp(value, expires),
+            },
+        }
+    }
+
+    /// Removes all expired items, including any left behind by writes
+    /// that exhausted their purge budget.
+    pub fn run_pending_tasks(&mut self) {
+        self.purge_expired();
+    }
+
+    /// Removes all expired items and returns how many were removed.
+    pub fn purge_expired(&mut self) -> usize {
+        let now = self.clock.now();
+        let removed = self.purge(now, None, None).0;
+        self.notify_removals();
+        removed
+    }
+
+    /// Returns the number of cached items that have not expired.
+    /// Unlike [Cache::len], this inspects every item.
+    pub fn len_unexpired(&self) -> usize {
+        let now = self.clock.now();
+        self.entries
+            .iter()
+            .filter(|entry| !self.is_expired(entry, now))
+            .count()
+    }
+
+    /// Returns whether the cache holds no items that have not expired.
+    /// Unlike [Cache::is_empty], this may inspect every item.
+    pub fn is_empty_unexpired(&self) -> bool {
+        let now = self.clock.now();
+        self.entries.iter().all(|entry| self.is_expired(entry, now))
+    }
+
+    /// Returns the expiration time of a value stored for the given key by [Cache::put],
+    /// replacing the entry in the given slot, if any.
+    fn expiration_for(&self, found: Option<usize>, key: &K, value: &V) -> Option<Instant> {
+        let now = self.clock.now();
+        let ttl = match &self.expiry {
+            Some(expiry) => {
+                let current = found.map(|slot| {
+                    let deadline = self.entries[slot].deadline.load(Ordering::Relaxed);
+                    self.epoch.instant(deadline)
+                });
+                match current {
+                    Some(deadline) if deadline.map_or(true, |deadline| deadline > now) => {
+                        let remaining = deadline.map(|deadline| deadline.duration_since(now));
+                        expiry.expire_after_update(key, value, remaining)
+                    }
+                    _ => expiry.expire_after_create(key, value),
+                }
+            }
+            None => self.default_ttl,
+        };
+
+        ttl.and_then(|ttl| now.checked_add(ttl))
+    }
+
+    /// Stores a value for the given key with the given hash as if by [Cache::put],
+    /// replacing the entry in the given slot, if any, and returns the slot of
+    /// the stored entry, unless it was rejected or removed to keep the cache within its bounds.
+    pub(crate) fn put_at(
+        &mut self,
+        hash: u64,
+        found: Option<usize>,
+        key: K,
+        value: V,
+    ) -> Option<usize> {
+        let expires = self.expiration_for(found, &key, &value);
+        let slot = self.insert_at(hash, found, key, value, expires)?;
+        self.entries[slot].implicit = true;
+        Some(slot)
+    }
+
+    /// Stores a value for the given key with the given hash, replacing the entry
+    /// in the given slot, if any, and returns the slot of the stored entry,
+    /// unless it was rejected or removed to keep the cache within its bounds.
+    pub(crate) fn insert_at(
+        &mut self,
+        hash: u64,
+        found: Option<usize>,
+        key: K,
+        value: V,
+        expires: Option<Instant>,
+    ) -> Option<usize> {
+        self.store(hash, found, key, value, expires, self.stale_ttl)
+    }
+
+    /// Implements [Cache::insert_at], keeping the entry for the given duration
+    /// once it expires.
+    fn store(
+        &mut self,
+        hash: u64,
+        found: Option<usize>,
+        key: K,
+        value: V,
+        expires: Option<Instant>,
+        grace: Duration,
+    ) -> Option<usize> {
+        let weight = match &self.weighing {
+            Some(weighing) => {
+                let weight = weighing.weigher.weigh(&key, &value);
+                if weighing.reject_oversized && weight > weighing.max_weight {
+                    if let Some(entry) = found.and_then(|slot| self.remove_entry(slot)) {
+                        let deadline = entry.deadline.into_inner();
+                        self.record_removal(key, entry.value, deadline, RemovalCause::Replaced);
+                        self.notify_removals();
+                    }
+
+                    return None;
+                }
+
+                weight
+            }
+            None => 0,
+        };
+
+        let now = self.clock.now();
+        let expires = self.jittered(expires, now);
+        let fresh_until = self.deadline(expires, now);
+        let deadline = self.epoch.stamp(fresh_until);
+        let tracked = stale_deadline(fresh_until, grace);
+        let written = self.epoch.stamp(Some(now));
+        let version = self.next_version();
+        let bounded = self.is_bounded();
+        let policy = self
+            .policy
+            .get_mut()
+            .expect("failed to acquire policy lock");
+        let (slot, old_weight, replaced) = match found {
+            Some(slot) => {
+                let entry = &mut self.entries[slot];
+                if let Some(expires) = entry.tracked {
+                    self.expirations.cancel(slot, expires);
+                }
+
+                let old_value = std::mem::replace(&mut entry.value, value);
+                let old_deadline = std::mem::replace(entry.deadline.get_mut(), deadline);
+                entry.expires = expires;
+                entry.tracked = tracked;
+                entry.implicit = false;
+                entry.grace = grace;
+                entry.written = written;
+                entry.version = version;
+                entry.refresh_failures = 0;
+                if bounded {
+                    policy.touch(slot);
+                }
+
+                let old_weight = std::mem::replace(&mut entry.weight, weight);
+                (slot, old_weight, Some((key, old_value, old_deadline)))
+            }
+            None => {
+                let slot = self.entries.insert(Entry {
+                    key,
+                    value,
+                    hash,
                     expires,
+                    tracked,
+                    deadline: AtomicU64::new(deadline),
+                    implicit: false,
+                    grace,
+                    recompute: Duration::ZERO,
+                    written,
+                    version,
+                    refresh_failures: 0,
+                    weight,
                 });
+
+                self.index.insert(hash, slot);
+                if bounded {
+                    policy.insert(slot, hash);
+                }
+
+                (slot, 0, None)
             }
+        };
+
+        if let Some((key, value, deadline)) = replaced {
+            self.record_removal(key, value, deadline, RemovalCause::Replaced);
+        }
+
+        if let Some(weighing) = &mut self.weighing {
+            weighing.total_weight = weighing.total_weight - old_weight + weight;
         }
+
+        if let Some(expires) = tracked {
+            self.expirations.schedule(slot, expires, self.epoch);
+        }
+
+        self.purge(now, self.purge_max_entries, self.purge_max_time);
+        self.evict_excess();
+        self.notify_removals();
+        self.entries.get(slot).map(|_| slot)
     }
-}
 
-#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
-struct Expiration<K> {
-    expires: Instant,
-    key: K,
-}
+    pub(crate) fn key_at(&self, slot: usize) -> &K {
+        &self.entries[slot].key
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use mock_instant::{Instant, MockClock};
-    use std::time::Duration;
+    pub(crate) fn value_at(&self, slot: usize) -> &V {
+        &self.entries[slot].value
+    }
 
-    #[test]
-    fn put_with_no_expiration() {
-        let mut cache = Cache::default();
-        cache.put("test_key".to_string(), "test_value");
-        assert_eq!(cache.map.len(), 1);
-        assert!(cache.map.contains_key("test_key"));
-        assert_eq!(cache.expirations.len(), 0);
+    pub(crate) fn expiration_at(&self, slot: usize) -> Option<Instant> {
+        let deadline = self.entries[slot].deadline.load(Ordering::Relaxed);
+        self.epoch.instant(deadline)
     }
 
-    #[test]
-    fn put_with_expiration() {
-        let mut cache = Cache::default();
-        cache.put_exp(
-            "test_key".to_string(),
-            "test_value",
-            Some(Instant::now() + Duration::from_secs(1)),
-        );
+    /// Modifies the value of the entry in the given slot, updating its weight,
+    /// and returns whether the entry remains within the cache's bounds.
+    /// Its expiration time is unchanged.
+    pub(crate) fn modify_at<F: FnOnce(&mut V)>(&mut self, slot: usize, modify: F) -> bool {
+        let version = self.next_version();
+        let entry = &mut self.entries[slot];
+        modify(&mut entry.value);
+        entry.version = version;
+        entry.refresh_failures = 0;
+        if let Some(weighing) = &mut self.weighing {
+            let weight = weighing.weigher.weigh(&entry.key, &entry.value);
+            weighing.total_weight = weighing.total_weight - entry.weight + weight;
+            entry.weight = weight;
+            if weighing.reject_oversized && weight > weighing.max_weight {
+                if let Some(entry) = self.remove_entry(slot) {
+                    let deadline = entry.deadline.into_inner();
+                    self.record_removal(entry.key, entry.value, deadline, RemovalCause::Evicted);
+                }
+            }
+        }
 
-        assert_eq!(cache.map.len(), 1);
-        assert!(cache.map.contains_key("test_key"));
-        assert_eq!(cache.expirations.len(), 1);
+        if self.is_bounded() && self.entries.get(slot).is_some() {
+            self.policy
+                .get_mut()
+                .expect("failed to acquire policy lock")
+                .touch(slot);
+            self.evict_excess();
+        }
 
-        MockClock::advance(Duration::from_secs(2));
-        cache.put("another_key".to_string(), "another_value");
+        self.notify_removals();
+        self.entries.get(slot).is_some()
+    }
 
-        assert_eq!(cache.map.len(), 1);
-        assert!(!cache.map.contains_key("test_key"));
-        assert_eq!(cache.expirations.len(), 0);
+    /// Removes the entry in the given slot for the given cause, notifying the listener, if any.
+    pub(crate) fn remove_at(&mut self, slot: usize, cause: RemovalCause) {
+        if let Some(entry) = self.remove_entry(slot) {
+            let deadline = entry.deadline.into_inner();
+            self.record_removal(entry.key, entry.value, deadline, cause);
+            self.notify_removals();
+        }
     }
 
-    #[test]
-    fn get_unexpired() {
-        let mut cache = Cache::default();
-        cache.put_exp(
-            "test_key".to_string(),
-            "test_value",
-            Some(Instant::now() + Duration::from_secs(1)),
-        );
+    /// Sets the expiration time of the entry in the given slot, as if it were stored
+    /// with [Cache::put_exp], and reschedules it accordingly.
+    pub(crate) fn set_expiration_at(&mut self, slot: usize, expires: Option<Instant>) {
+        let fresh_until = self.deadline(expires, self.clock.now());
+        let entry = &mut self.entries[slot];
+        if let Some(expires) = entry.tracked {
+            self.expirations.cancel(slot, expires);
+        }
 
-        assert_eq!(cache.get(&"test_key".to_string()), Some(&"test_value"));
+        let tracked = stale_deadline(fresh_until, entry.grace);
+        entry.expires = expires;
+        entry.tracked = tracked;
+        entry.implicit = false;
+        *entry.deadline.get_mut() = self.epoch.stamp(fresh_until);
+        if let Some(expires) = tracked {
+            self.expirations.schedule(slot, expires, self.epoch);
+        }
     }
 
-    #[test]
-    fn get_expired() {
-        let mut cache = Cache::default();
-        cache.put_exp(
-            "test_key".to_string(),
-            "test_value",
-            Some(Instant::now() + Duration::from_secs(1)),
-        );
+    /// Removes expired items within the cache's purge budget, or a fixed number
+    /// of them if it has none, and returns whether all expired items were removed.
+    pub(crate) fn purge_chunk(&mut self) -> bool {
+        let now = self.clock.now();
+        let max_entries = match (self.purge_max_entries, self.purge_max_time) {
+            (None, None) => Some(PURGE_CHUNK),
+            (max_entries, _) => max_entries,
+        };
+
+        let complete = self.purge(now, max_entries, self.purge_max_time).1;
+        self.notify_removals();
+        complete
+    }
 
-        MockClock::advance(Duration::from_secs(2));
+    /// Removes items that expired by the given time, until either budget is exhausted,
+    /// and returns how many were removed, along with whether all expired items were.
+    /// The time budget is checked after each batch.
+    fn purge(
+        &mut self,
+        now: Instant,
+        max_entries: Option<usize>,
+        max_time: Option<Duration>,
+    ) -> (usize, bool) {
+        // A time budget too long to be represented is no budget at all.
+        let stop = max_time.and_then(|max_time| self.clock.now().checked_add(max_time));
+        let mut budget = max_entries.unwrap_or(usize::MAX);
+        let mut expired = Vec::new();
+        let mut removed = 0;
+        let mut complete = false;
+        while !complete && budget > 0 {
+            let limit = budget.min(PURGE_BATCH);
+            complete = self
+                .expirations
+                .expired(now, self.epoch, limit, &mut expired);
+            budget -= limit;
 
-        assert_eq!(cache.get(&"test_key".to_string()), None);
+            for slot in expired.drain(..) {
+                let entry = match self.entries.get_mut(slot) {
+                    Some(entry) => entry,
+                    None => continue,
+                };
+
+                // Entries whose expiration time was extended upon retrieval are rescheduled.
+                let deadline = self.epoch.instant(*entry.deadline.get_mut());
+                match stale_deadline(deadline, entry.grace) {
+                    Some(deadline) if deadline <= now => {
+                        if let Some(entry) = self.remove_entry(slot) {
+                            self.record_removal(
+                                entry.key,
+                                entry.value,
+                                entry.deadline.into_inner(),
+                                RemovalCause::Expired,
+                            );
+                        }
+
+                        removed += 1;
+                    }
+                    deadline => {
+                        entry.tracked = deadline;
+                        if let Some(deadline) = deadline {
+                            self.expirations.schedule(slot, deadline, self.epoch);
+                        }
+                    }
+                }
+            }
+
+            if stop.map_or(false, |stop| self.clock.now() >= stop) {
+                break;
+            }
+        }
+
+        (removed, complete)
     }
 
-    #[test]
-    fn delete_unexpired() {
-        let mut cache = Cache::default();
-        cache.put_exp(
-            "test_key".to_string(),
-            "test_value",
-            Some(Instant::now() + Duration::from_secs(1)),
-        );
+    fn is_expired(&self, entry: &Entry<K, V>, now: Instant) -> bool {
+        self.epoch
+            .expired(entry.deadline.load(Ordering::Relaxed), now)
+    }
 
-        cache.delete(&"test_key".to_string());
-        assert_eq!(cache.map.len(), 0);
-        assert_eq!(cache.expirations.len(), 0);
+    /// Returns the slot of the entry holding the given key with the given hash, if any.
+    fn find<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        self.index
+            .chain(hash)
+            .find(|&slot| self.entries[slot].key.borrow() == key)
+    }
+
+    fn evict_excess(&mut self) {
+        let mut evicted = false;
+        while self.exceeds_bounds() {
+            let slot = match self
+                .policy
+                .get_mut()
+                .expect("failed to acquire policy lock")
+                .evict()
+            {
+                Some(slot) => slot,
+                None => break,
+            };
+
+            if let Some(entry) = self.unlink_entry(slot) {
+                let deadline = entry.deadline.into_inner();
+                self.record_removal(entry.key, entry.value, deadline, RemovalCause::Evicted);
+            }
+
+            evicted = true;
+        }
+
+        // A cache bounded only by weight informs its policy of the number of entries
+        // that fit within the maximum weight, as observed once it is full.
+        if evicted && self.capacity.is_none() && self.entries.len() > self.policy_capacity {
+            self.policy_capacity = self.entries.len();
+            self.policy
+                .get_mut()
+                .expect("failed to acquire policy lock")
+                .set_capacity(self.policy_capacity);
+        }
+    }
+
+    /// Records the removal of an entry with the given deadline stamp for the listener,
+    /// if any. An entry that had already expired is recorded as such, whatever the cause.
+    fn record_removal(&mut self, key: K, value: V, deadline: u64, cause: RemovalCause) {
+        if let Some(notifier) = &mut self.notifier {
+            let cause = if self.epoch.expired(deadline, self.clock.now()) {
+                RemovalCause::Expired
+            } else {
+                cause
+            };
+
+            notifier.push(key, value, cause);
+        }
+    }
+
+    /// Removes the entry in the given slot, including from the eviction policy.
+    fn remove_entry(&mut self, slot: usize) -> Option<Entry<K, V>> {
+        let entry = self.unlink_entry(slot)?;
+        if self.is_bounded() {
+            self.policy
+                .get_mut()
+                .expect("failed to acquire policy lock")
+                .remove(slot);
+        }
+
+        Some(entry)
+    }
+
+    /// Removes the entry in the given slot, along with its tracked expiration
+    /// and weight, but not from the eviction policy.
+    fn unlink_entry(&mut self, slot: usize) -> Option<Entry<K, V>> {
+        let entry = self.entries.remove(slot)?;
+        self.index.remove(entry.hash, slot);
+        if let Some(expires) = entry.tracked {
+            self.expirations.cancel(slot, expires);
+        }
+
+        if let Some(weighing) = &mut self.weighing {
+            weighing.total_weight -= entry.weight;
+        }
+
+        Some(entry)
+    }
+}
+
+/// Returns the time until which an entry expiring at the given time is kept
+/// while stale, or `None` if it is kept indefinitely.
+fn stale_deadline(deadline: Option<Instant>, grace: Duration) -> Option<Instant> {
+    deadline.and_then(|deadline| deadline.checked_add(grace))
+}
+
+fn hash_key<Q: Hash + ?Sized, S: BuildHasher>(hasher: &S, key: &Q) -> u64 {
+    let mut state = hasher.build_hasher();
+    key.hash(&mut state);
+    state.finish()
+}
+
+/// Index of the expiration times of a cache's entries.
+#[derive(Debug)]
+enum Expirations {
+    Ordered(BTreeSet<Expiration>),
+    Wheel(TimingWheel),
+}
+
+impl Expirations {
+    fn schedule(&mut self, slot: usize, expires: Instant, epoch: Epoch) {
+        match self {
+            Self::Ordered(expirations) => {
+                expirations.insert(Expiration { expires, slot });
+            }
+            Self::Wheel(wheel) => wheel.schedule(slot, epoch.stamp(Some(expires))),
+        }
+    }
+
+    fn cancel(&mut self, slot: usize, expires: Instant) {
+        match self {
+            Self::Ordered(expirations) => {
+                expirations.remove(&Expiration { expires, slot });
+            }
+            Self::Wheel(wheel) => wheel.cancel(slot),
+        }
+    }
+
+    /// Removes the entries that expired by the given time, returning their slots.
+    /// Removes up to `limit` entries that expired by the given time, appending their slots
+    /// to `expired`, and returns whether all expired entries have been removed.
+    fn expired(
+        &mut self,
+        now: Instant,
+        epoch: Epoch,
+        limit: usize,
+        expired: &mut Vec<usize>,
+    ) -> bool {
+        match self {
+            Self::Ordered(expirations) => {
+                for _ in 0..limit {
+                    match expirations.iter().next().copied() {
+                        Some(item) if item.expires <= now => {
+                            expirations.remove(&item);
+                            expired.push(item.slot);
+                        }
+                        _ => return true,
+                    }
+                }
+
+                false
+            }
+            Self::Wheel(wheel) => wheel.advance(epoch.stamp(Some(now)), limit, expired),
+        }
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        match self {
+            Self::Ordered(expirations) => expirations.len(),
+            Self::Wheel(wheel) => wheel.len(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+struct Expiration {
+    expires: Instant,
+    slot: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::entry;
+    use crate::policy::{Fifo, TinyLfu};
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::BuildHasherDefault;
+    use std::sync::Arc;
+
+    type Removed = Arc<Mutex<Vec<(String, &'static str, RemovalCause)>>>;
+
+    fn record_removals(removed: &Removed) -> impl Fn(String, &'static str, RemovalCause) {
+        let removed = removed.clone();
+        move |key, value, cause| {
+            removed
+                .lock()
+                .expect("failed to acquire lock")
+                .push((key, value, cause))
+        }
+    }
+
+    impl<V, P, S: BuildHasher, C> Cache<String, V, P, S, C> {
+        fn contains(&self, key: &str) -> bool {
+            let key = key.to_string();
+            self.index
+                .chain(hash_key(&self.hasher, &key))
+                .any(|slot| self.entries[slot].key == key)
+        }
+    }
+
+    struct MaxAge;
+
+    impl Expiry<String, u64> for MaxAge {
+        fn expire_after_create(&self, _: &String, max_age: &u64) -> Option<Duration> {
+            Some(Duration::from_secs(*max_age))
+        }
+    }
+
+    struct Sliding;
+
+    impl Expiry<String, u64> for Sliding {
+        fn expire_after_create(&self, _: &String, max_age: &u64) -> Option<Duration> {
+            Some(Duration::from_secs(*max_age))
+        }
+
+        fn expire_after_update(
+            &self,
+            _: &String,
+            _: &u64,
+            current: Option<Duration>,
+        ) -> Option<Duration> {
+            current
+        }
+
+        fn expire_after_read(
+            &self,
+            _: &String,
+            max_age: &u64,
+            _: Option<Duration>,
+        ) -> Option<Duration> {
+            Some(Duration::from_secs(*max_age))
+        }
+    }
+
+    #[test]
+    fn put_with_no_expiration() {
+        let mut cache = Cache::default();
+        cache.put("test_key".to_string(), "test_value");
+        assert_eq!(cache.entries.len(), 1);
+        assert!(cache.contains("test_key"));
+        assert_eq!(cache.expirations.len(), 0);
+    }
+
+    #[test]
+    fn put_with_expiration() {
+        let clock = ManualClock::new();
+        let mut cache = CacheBuilder::new().clock(clock.clone()).build();
+        cache.put_exp(
+            "test_key".to_string(),
+            "test_value",
+            Some(clock.now() + Duration::from_secs(1)),
+        );
+
+        assert_eq!(cache.entries.len(), 1);
+        assert!(cache.contains("test_key"));
+        assert_eq!(cache.expirations.len(), 1);
+
+        clock.advance(Duration::from_secs(2));
+        cache.put("another_key".to_string(), "another_value");
+
+        assert_eq!(cache.entries.len(), 1);
+        assert!(!cache.contains("test_key"));
+        assert_eq!(cache.expirations.len(), 0);
+    }
+
+    #[test]
+    fn put_treats_overflowing_ttl_as_never_expiring() {
+        let mut cache = CacheBuilder::new().default_ttl(Duration::MAX).build();
+        cache.put("test_key_1".to_string(), "test_value");
+        cache.put_ttl("test_key_2".to_string(), "test_value", Duration::MAX);
+        cache.compute("test_key_3".to_string(), |_| {
+            entry::Op::PutTtl("test_value", Duration::MAX)
+        });
+
+        assert_eq!(cache.entries.len(), 3);
+        assert_eq!(cache.expirations.len(), 0);
+        assert_eq!(cache.next_expiration(), None);
+        assert_eq!(cache.get("test_key_3"), Some(&"test_value"));
+    }
+
+    #[test]
+    fn get_unexpired() {
+        let mut cache = Cache::default();
+        cache.put_exp(
+            "test_key".to_string(),
+            "test_value",
+            Some(Instant::now() + Duration::from_secs(1)),
+        );
+
+        assert_eq!(cache.get(&"test_key".to_string()), Some(&"test_value"));
+    }
+
+    #[test]
+    fn get_expired() {
+        let clock = ManualClock::new();
+        let mut cache = CacheBuilder::new().clock(clock.clone()).build();
+        cache.put_exp(
+            "test_key".to_string(),
+            "test_value",
+            Some(clock.now() + Duration::from_secs(1)),
+        );
+
+        clock.advance(Duration::from_secs(2));
+
+        assert_eq!(cache.get(&"test_key".to_string()), None);
+    }
+
+    #[test]
+    fn delete_unexpired() {
+        let mut cache = Cache::default();
+        cache.put_exp(
+            "test_key".to_string(),
+            "test_value",
+            Some(Instant::now() + Duration::from_secs(1)),
+        );
+
+        cache.delete(&"test_key".to_string());
+        assert_eq!(cache.entries.len(), 0);
+        assert_eq!(cache.expirations.len(), 0);
+    }
+
+    #[test]
+    fn put_beyond_capacity_evicts_least_recently_used() {
+        let mut cache = Cache::with_capacity(2);
+        cache.put("key_1".to_string(), "value_1");
+        cache.put("key_2".to_string(), "value_2");
+
+        assert_eq!(cache.get(&"key_1".to_string()), Some(&"value_1"));
+        cache.put("key_3".to_string(), "value_3");
+
+        assert_eq!(cache.entries.len(), 2);
+        assert!(cache.contains("key_1"));
+        assert!(!cache.contains("key_2"));
+        assert!(cache.contains("key_3"));
+    }
+
+    #[test]
+    fn put_beyond_capacity_prefers_expired() {
+        let clock = ManualClock::new();
+        let mut cache = CacheBuilder::new()
+            .max_entries(2)
+            .clock(clock.clone())
+            .build();
+        cache.put("key_1".to_string(), "value_1");
+        cache.put_exp(
+            "key_2".to_string(),
+            "value_2",
+            Some(clock.now() + Duration::from_secs(1)),
+        );
+
+        clock.advance(Duration::from_secs(2));
+        cache.put("key_3".to_string(), "value_3");
+
+        assert_eq!(cache.entries.len(), 2);
+        assert!(cache.contains("key_1"));
+        assert!(!cache.contains("key_2"));
+        assert!(cache.contains("key_3"));
+        assert_eq!(cache.expirations.len(), 0);
+    }
+
+    #[test]
+    fn replace_at_capacity_does_not_evict() {
+        let mut cache = Cache::with_capacity(2);
+        cache.put("key_1".to_string(), "value_1");
+        cache.put("key_2".to_string(), "value_2");
+        cache.put("key_1".to_string(), "new_value_1");
+        cache.put("key_3".to_string(), "value_3");
+
+        assert_eq!(cache.entries.len(), 2);
+        assert_eq!(cache.get(&"key_1".to_string()), Some(&"new_value_1"));
+        assert!(!cache.contains("key_2"));
+    }
+
+    #[test]
+    fn put_beyond_capacity_evicts_by_policy() {
+        let mut cache = Cache::with_policy(2, Fifo::default());
+        cache.put("key_1".to_string(), "value_1");
+        cache.put("key_2".to_string(), "value_2");
+
+        assert_eq!(cache.get(&"key_1".to_string()), Some(&"value_1"));
+        cache.put("key_3".to_string(), "value_3");
+
+        assert_eq!(cache.entries.len(), 2);
+        assert!(!cache.contains("key_1"));
+        assert!(cache.contains("key_2"));
+        assert!(cache.contains("key_3"));
+    }
+
+    #[test]
+    fn put_beyond_capacity_resists_scan() {
+        let mut cache = Cache::with_policy(100, TinyLfu::default());
+        for i in 0..100 {
+            cache.put(format!("key_{}", i), "value");
+        }
+
+        for _ in 0..3 {
+            cache.get(&"key_1".to_string());
+            cache.get(&"key_2".to_string());
+        }
+
+        for i in 0..200 {
+            cache.put(format!("scan_key_{}", i), "value");
+        }
+
+        assert_eq!(cache.entries.len(), 100);
+        assert!(cache.contains("key_1"));
+        assert!(cache.contains("key_2"));
+    }
+
+    #[test]
+    fn put_beyond_max_weight_resists_scan() {
+        let mut cache = CacheBuilder::new()
+            .weigher(100, |_: &String, _: &&str| 1)
+            .policy(TinyLfu::default())
+            .hasher(BuildHasherDefault::<DefaultHasher>::default())
+            .build();
+        for i in 0..101 {
+            cache.put(format!("key_{}", i), "value");
+        }
+
+        for _ in 0..3 {
+            for i in 1..11 {
+                cache.get(&format!("key_{}", i));
+            }
+        }
+
+        for i in 0..200 {
+            cache.put(format!("scan_key_{}", i), "value");
+        }
+
+        assert_eq!(cache.entries.len(), 100);
+        assert!((1..11).all(|i| cache.contains(&format!("key_{}", i))));
+    }
+
+    #[test]
+    fn put_beyond_max_weight_evicts_least_recently_used() {
+        let mut cache = Cache::with_weigher(10, |_: &String, value: &&str| value.len() as u64);
+        cache.put("key_1".to_string(), "1234");
+        cache.put("key_2".to_string(), "1234");
+        cache.put("key_3".to_string(), "1234");
+
+        assert_eq!(cache.entries.len(), 2);
+        assert!(!cache.contains("key_1"));
+        assert_eq!(cache.weight(), 8);
+
+        cache.put("key_2".to_string(), "12");
+        assert_eq!(cache.weight(), 6);
+
+        cache.delete(&"key_2".to_string());
+        assert_eq!(cache.weight(), 4);
+    }
+
+    #[test]
+    fn put_oversized_rejects() {
+        let mut cache = Cache::with_weigher(10, |_: &String, value: &&str| value.len() as u64);
+        cache.put("key_1".to_string(), "1234");
+        cache.put("key_2".to_string(), "1234");
+        cache.put("key_2".to_string(), "12345678901");
+
+        assert_eq!(cache.entries.len(), 1);
+        assert!(cache.contains("key_1"));
+        assert_eq!(cache.weight(), 4);
+    }
+
+    #[test]
+    fn put_oversized_admits() {
+        let mut cache = Cache::with_weigher(10, |_: &String, value: &&str| value.len() as u64);
+        cache.set_reject_oversized(false);
+        cache.put("key_1".to_string(), "1234");
+        cache.put("key_2".to_string(), "12345678901");
+
+        assert_eq!(cache.entries.len(), 1);
+        assert!(cache.contains("key_2"));
+        assert_eq!(cache.weight(), 11);
+    }
+
+    #[test]
+    fn put_with_expiration_releases_weight() {
+        let clock = ManualClock::new();
+        let mut cache = CacheBuilder::new()
+            .weigher(10, |_: &String, value: &&str| value.len() as u64)
+            .clock(clock.clone())
+            .build();
+        cache.put_exp(
+            "key_1".to_string(),
+            "1234",
+            Some(clock.now() + Duration::from_secs(1)),
+        );
+
+        clock.advance(Duration::from_secs(2));
+        cache.put("key_2".to_string(), "12");
+
+        assert_eq!(cache.entries.len(), 1);
+        assert_eq!(cache.weight(), 2);
+    }
+
+    #[test]
+    fn put_with_default_ttl() {
+        let clock = ManualClock::new();
+        let mut cache = CacheBuilder::new()
+            .clock(clock.clone())
+            .default_ttl(Duration::from_secs(1))
+            .build();
+        cache.put("test_key".to_string(), "test_value");
+        cache.put_exp("another_key".to_string(), "another_value", None);
+
+        assert_eq!(cache.expirations.len(), 1);
+
+        clock.advance(Duration::from_secs(2));
+
+        assert_eq!(cache.get(&"test_key".to_string()), None);
+        assert_eq!(
+            cache.get(&"another_key".to_string()),
+            Some(&"another_value")
+        );
+    }
+
+    #[test]
+    fn put_ttl_expires() {
+        let clock = ManualClock::new();
+        let mut cache = CacheBuilder::new().clock(clock.clone()).build();
+        cache.put_ttl("test_key".to_string(), "test_value", Duration::from_secs(1));
+
+        assert_eq!(cache.get(&"test_key".to_string()), Some(&"test_value"));
+
+        clock.advance(Duration::from_secs(2));
+
+        assert_eq!(cache.get(&"test_key".to_string()), None);
+    }
+
+    #[test]
+    fn get_extends_time_to_idle() {
+        let clock = ManualClock::new();
+        let mut cache = CacheBuilder::new()
+            .clock(clock.clone())
+            .time_to_idle(Duration::from_secs(2))
+            .build();
+        cache.put("test_key".to_string(), "test_value");
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(cache.get(&"test_key".to_string()), Some(&"test_value"));
+
+        clock.advance(Duration::from_millis(1500));
+        assert_eq!(cache.get(&"test_key".to_string()), Some(&"test_value"));
+
+        clock.advance(Duration::from_millis(2500));
+        assert_eq!(cache.get(&"test_key".to_string()), None);
+    }
+
+    #[test]
+    fn put_reschedules_accessed_idle_entries() {
+        let clock = ManualClock::new();
+        let mut cache = CacheBuilder::new()
+            .clock(clock.clone())
+            .time_to_idle(Duration::from_secs(2))
+            .build();
+        cache.put("test_key".to_string(), "test_value");
+
+        clock.advance(Duration::from_secs(1));
+        cache.get(&"test_key".to_string());
+
+        clock.advance(Duration::from_millis(1500));
+        cache.put("another_key".to_string(), "another_value");
+
+        assert!(cache.contains("test_key"));
+        assert_eq!(cache.expirations.len(), 2);
+
+        clock.advance(Duration::from_secs(1));
+        cache.put("another_key".to_string(), "another_value");
+
+        assert!(!cache.contains("test_key"));
+        assert_eq!(cache.expirations.len(), 1);
+    }
+
+    #[test]
+    fn version_of_does_not_count_as_access() {
+        let clock = ManualClock::new();
+        let mut cache = CacheBuilder::new()
+            .clock(clock.clone())
+            .time_to_idle(Duration::from_secs(2))
+            .build();
+        cache.put("test_key".to_string(), "test_value");
+        let version = cache.version_of("test_key");
+        assert!(version.is_some());
+
+        clock.advance(Duration::from_millis(1500));
+        assert_eq!(cache.version_of("test_key"), version);
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(cache.version_of("test_key"), None);
+        assert_eq!(cache.get("test_key"), None);
+
+        cache.put("test_key".to_string(), "test_value");
+        assert_ne!(cache.version_of("test_key"), version);
+    }
+
+    #[test]
+    fn get_respects_max_lifetime_of_idle_entries() {
+        let clock = ManualClock::new();
+        let mut cache = CacheBuilder::new()
+            .clock(clock.clone())
+            .time_to_idle(Duration::from_secs(2))
+            .default_ttl(Duration::from_secs(3))
+            .build();
+        cache.put("test_key".to_string(), "test_value");
+
+        for _ in 0..2 {
+            clock.advance(Duration::from_secs(1));
+            assert_eq!(cache.get(&"test_key".to_string()), Some(&"test_value"));
+        }
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(cache.get(&"test_key".to_string()), None);
+    }
+
+    #[test]
+    fn time_to_idle_overflowing_instant_never_expires() {
+        let clock = ManualClock::new();
+        let mut cache = CacheBuilder::new()
+            .clock(clock.clone())
+            .time_to_idle(Duration::MAX)
+            .build();
+        cache.put("test_key_1".to_string(), "test_value");
+        cache.put_ttl(
+            "test_key_2".to_string(),
+            "test_value",
+            Duration::from_secs(1),
+        );
+        assert_eq!(cache.get("test_key_1"), Some(&"test_value"));
+        assert_eq!(
+            cache.next_expiration(),
+            Some(clock.now() + Duration::from_secs(1))
+        );
+
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(cache.get("test_key_1"), Some(&"test_value"));
+        assert_eq!(cache.get("test_key_2"), None);
+    }
+
+    #[test]
+    fn expiry_overflowing_instant_never_expires() {
+        let clock = ManualClock::new();
+        let mut cache = CacheBuilder::new()
+            .clock(clock.clone())
+            .expiry(Sliding)
+            .build();
+        cache.put("test_key".to_string(), u64::MAX);
+        assert_eq!(cache.next_expiration(), None);
+        assert_eq!(cache.get("test_key"), Some(&u64::MAX));
+
+        cache.put("another_key".to_string(), 1);
+        cache.put("another_key".to_string(), u64::MAX);
+        assert_eq!(cache.get("another_key"), Some(&u64::MAX));
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(cache.get("another_key"), Some(&u64::MAX));
+    }
+
+    #[test]
+    fn get_skips_expire_after_read_for_explicit_expiration() {
+        let clock = ManualClock::new();
+        let mut cache = CacheBuilder::new()
+            .clock(clock.clone())
+            .expiry(Sliding)
+            .build();
+        cache.put_ttl("test_key".to_string(), 10, Duration::from_secs(1));
+        assert_eq!(cache.get("test_key"), Some(&10));
+
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(cache.get("test_key"), None);
+    }
+
+    #[test]
+    fn put_derives_ttl_from_value() {
+        let clock = ManualClock::new();
+        let mut cache = CacheBuilder::new()
+            .clock(clock.clone())
+            .default_ttl(Duration::from_secs(10))
+            .expiry(MaxAge)
+            .build();
+        cache.put("test_key".to_string(), 1);
+        cache.put("another_key".to_string(), 5);
+        cache.put_exp("explicit_key".to_string(), 1, None);
+
+        clock.advance(Duration::from_secs(2));
+
+        assert_eq!(cache.get(&"test_key".to_string()), None);
+        assert_eq!(cache.get(&"another_key".to_string()), Some(&5));
+        assert_eq!(cache.get(&"explicit_key".to_string()), Some(&1));
+    }
+
+    #[test]
+    fn put_consults_expire_after_update() {
+        let clock = ManualClock::new();
+        let mut cache = CacheBuilder::new()
+            .clock(clock.clone())
+            .expiry(Sliding)
+            .build();
+        cache.put("test_key".to_string(), 2);
+
+        clock.advance(Duration::from_secs(1));
+        cache.put("test_key".to_string(), 5);
+
+        clock.advance(Duration::from_millis(1500));
+        assert_eq!(cache.get(&"test_key".to_string()), None);
+
+        cache.put("test_key".to_string(), 2);
+        assert_eq!(cache.get(&"test_key".to_string()), Some(&2));
+    }
+
+    #[test]
+    fn get_consults_expire_after_read() {
+        let clock = ManualClock::new();
+        let mut cache = CacheBuilder::new()
+            .clock(clock.clone())
+            .expiry(Sliding)
+            .build();
+        cache.put("test_key".to_string(), 2);
+
+        for _ in 0..2 {
+            clock.advance(Duration::from_millis(1500));
+            assert_eq!(cache.get(&"test_key".to_string()), Some(&2));
+        }
+
+        cache.put("another_key".to_string(), 10);
+        assert!(cache.contains("test_key"));
+        assert_eq!(cache.expirations.len(), 2);
+
+        clock.advance(Duration::from_millis(2500));
+        cache.put("another_key".to_string(), 10);
+
+        assert!(!cache.contains("test_key"));
+        assert_eq!(cache.expirations.len(), 1);
+    }
+
+    #[test]
+    fn put_with_expiration_tracked_by_timing_wheel() {
+        let clock = ManualClock::new();
+        let mut cache = CacheBuilder::new()
+            .timing_wheel(true)
+            .clock(clock.clone())
+            .build();
+        cache.put_ttl("test_key".to_string(), "test_value", Duration::from_secs(1));
+        cache.put_ttl(
+            "another_key".to_string(),
+            "another_value",
+            Duration::from_secs(3),
+        );
+        cache.put_ttl("test_key".to_string(), "test_value", Duration::from_secs(2));
+
+        assert_eq!(cache.expirations.len(), 2);
+
+        clock.advance(Duration::from_millis(1500));
+        cache.put("key".to_string(), "value");
+
+        assert!(cache.contains("test_key"));
+
+        clock.advance(Duration::from_secs(1));
+        cache.put("key".to_string(), "value");
+
+        assert!(!cache.contains("test_key"));
+        assert!(cache.contains("another_key"));
+        assert_eq!(cache.expirations.len(), 1);
+
+        cache.delete(&"another_key".to_string());
+        assert_eq!(cache.expirations.len(), 0);
+    }
+
+    #[test]
+    fn put_reschedules_idle_entries_in_timing_wheel() {
+        let clock = ManualClock::new();
+        let mut cache = CacheBuilder::new()
+            .time_to_idle(Duration::from_secs(2))
+            .timing_wheel(true)
+            .clock(clock.clone())
+            .build();
+        cache.put("test_key".to_string(), "test_value");
+
+        clock.advance(Duration::from_secs(1));
+        cache.get(&"test_key".to_string());
+
+        clock.advance(Duration::from_millis(1500));
+        cache.put("another_key".to_string(), "another_value");
+
+        assert!(cache.contains("test_key"));
+
+        clock.advance(Duration::from_secs(1));
+        cache.put("another_key".to_string(), "another_value");
+
+        assert!(!cache.contains("test_key"));
+        assert_eq!(cache.expirations.len(), 1);
+    }
+
+    #[test]
+    fn put_with_unordered_keys() {
+        #[derive(Debug, Eq, Hash, PartialEq)]
+        struct Key(Vec<u8>);
+
+        let clock = ManualClock::new();
+        let mut cache = CacheBuilder::new()
+            .max_entries(1)
+            .clock(clock.clone())
+            .build();
+        cache.put_ttl(Key(vec![1]), "value_1", Duration::from_secs(1));
+        assert_eq!(cache.get(&Key(vec![1])), Some(&"value_1"));
+
+        cache.put(Key(vec![2]), "value_2");
+        assert_eq!(cache.get(&Key(vec![1])), None);
+        assert_eq!(cache.expirations.len(), 0);
+
+        cache.delete(&Key(vec![2]));
+        assert_eq!(cache.entries.len(), 0);
+    }
+
+    #[test]
+    fn put_with_colliding_hashes() {
+        #[derive(Default)]
+        struct ConstantHasher;
+
+        impl Hasher for ConstantHasher {
+            fn finish(&self) -> u64 {
+                0
+            }
+
+            fn write(&mut self, _: &[u8]) {}
+        }
+
+        let mut cache = CacheBuilder::new()
+            .hasher(BuildHasherDefault::<ConstantHasher>::default())
+            .build();
+        cache.put("key_1".to_string(), "value_1");
+        cache.put("key_2".to_string(), "value_2");
+        cache.put("key_3".to_string(), "value_3");
+        cache.delete(&"key_2".to_string());
+
+        assert_eq!(cache.entries.len(), 2);
+        assert_eq!(cache.get(&"key_1".to_string()), Some(&"value_1"));
+        assert_eq!(cache.get(&"key_2".to_string()), None);
+        assert_eq!(cache.get(&"key_3".to_string()), Some(&"value_3"));
+    }
+
+    #[test]
+    fn get_with_borrowed_key() {
+        let clock = ManualClock::new();
+        let mut cache = CacheBuilder::new().clock(clock.clone()).build();
+        cache.put("test_key".to_string(), "test_value");
+        cache.put_ttl(
+            "another_key".to_string(),
+            "another_value",
+            Duration::from_secs(1),
+        );
+
+        assert_eq!(cache.get("test_key"), Some(&"test_value"));
+        assert!(cache.contains_key("test_key"));
+        assert!(cache.contains_key("another_key"));
+        assert!(!cache.contains_key("missing_key"));
+
+        clock.advance(Duration::from_secs(2));
+        assert!(!cache.contains_key("another_key"));
+
+        cache.delete("test_key");
+        assert!(!cache.contains_key("test_key"));
+        assert_eq!(cache.expirations.len(), 1);
+    }
+
+    #[test]
+    fn put_purges_within_entry_budget() {
+        for &timing_wheel in &[false, true] {
+            let clock = ManualClock::new();
+            let mut cache = CacheBuilder::new()
+                .purge_max_entries(2)
+                .timing_wheel(timing_wheel)
+                .clock(clock.clone())
+                .build();
+            for i in 0..5 {
+                cache.put_ttl(format!("key_{}", i), "value", Duration::from_secs(1));
+            }
+
+            clock.advance(Duration::from_secs(2));
+            cache.put("test_key".to_string(), "test_value");
+
+            assert_eq!(cache.entries.len(), 4);
+            assert_eq!(cache.expirations.len(), 3);
+
+            cache.put("test_key".to_string(), "test_value");
+            assert_eq!(cache.entries.len(), 2);
+
+            cache.run_pending_tasks();
+            assert_eq!(cache.entries.len(), 1);
+            assert_eq!(cache.expirations.len(), 0);
+        }
+    }
+
+    #[test]
+    fn put_ignores_time_budget_overflowing_instant() {
+        let clock = ManualClock::new();
+        let mut cache = CacheBuilder::new()
+            .purge_max_time(Duration::MAX)
+            .clock(clock.clone())
+            .build();
+        cache.put_ttl("key_1".to_string(), "value_1", Duration::from_secs(1));
+
+        clock.advance(Duration::from_secs(2));
+        cache.put("test_key".to_string(), "test_value");
+        assert_eq!(cache.entries.len(), 1);
+    }
+
+    #[test]
+    fn put_purges_within_time_budget() {
+        let clock = ManualClock::new();
+        let mut cache = CacheBuilder::new()
+            .purge_max_time(Duration::ZERO)
+            .clock(clock.clone())
+            .build();
+        for i in 0..100 {
+            cache.put_ttl(format!("key_{}", i), "value", Duration::from_secs(1));
+        }
+
+        clock.advance(Duration::from_secs(2));
+        cache.put("test_key".to_string(), "test_value");
+
+        assert_eq!(cache.entries.len(), 101 - PURGE_BATCH);
+
+        cache.run_pending_tasks();
+        assert_eq!(cache.entries.len(), 1);
+    }
+
+    #[test]
+    fn purge_expired_counts_removed_items() {
+        let clock = ManualClock::new();
+        let mut cache = CacheBuilder::new().clock(clock.clone()).build();
+        cache.put_ttl("key_1".to_string(), "value_1", Duration::from_secs(1));
+        cache.put_ttl("key_2".to_string(), "value_2", Duration::from_secs(1));
+        cache.put_ttl("key_3".to_string(), "value_3", Duration::from_secs(3));
+        cache.put("key_4".to_string(), "value_4");
+
+        assert_eq!(cache.purge_expired(), 0);
+
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(cache.len(), 4);
+        assert_eq!(cache.len_unexpired(), 2);
+
+        assert_eq!(cache.purge_expired(), 2);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn next_expiration_reports_earliest() {
+        let clock = ManualClock::new();
+        let mut cache = CacheBuilder::new().clock(clock.clone()).build();
+        assert_eq!(cache.next_expiration(), None);
+
+        let now = clock.now();
+        cache.put_ttl("key_1".to_string(), "value_1", Duration::from_secs(3));
+        cache.put_ttl("key_2".to_string(), "value_2", Duration::from_secs(1));
+        assert_eq!(cache.next_expiration(), Some(now + Duration::from_secs(1)));
+
+        cache.delete("key_2");
+        assert_eq!(cache.next_expiration(), Some(now + Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn is_empty_optionally_excludes_expired() {
+        let clock = ManualClock::new();
+        let mut cache = CacheBuilder::new().clock(clock.clone()).build();
+        assert!(cache.is_empty());
+        assert!(cache.is_empty_unexpired());
+
+        cache.put_ttl("test_key".to_string(), "test_value", Duration::from_secs(1));
+        assert!(!cache.is_empty_unexpired());
+
+        clock.advance(Duration::from_secs(2));
+        assert!(!cache.is_empty());
+        assert!(cache.is_empty_unexpired());
+    }
+
+    #[test]
+    fn listener_receives_removal_causes() {
+        let clock = ManualClock::new();
+        let removed = Removed::default();
+        let mut cache = CacheBuilder::new()
+            .max_entries(2)
+            .clock(clock.clone())
+            .removal_listener(record_removals(&removed))
+            .build();
+
+        cache.put("key_1".to_string(), "value_1");
+        cache.put("key_1".to_string(), "value_2");
+        cache.put_ttl("key_2".to_string(), "value_3", Duration::from_secs(1));
+        cache.put("key_3".to_string(), "value_4");
+        cache.delete("key_3");
+
+        clock.advance(Duration::from_secs(2));
+        cache.put_ttl("key_4".to_string(), "value_5", Duration::from_secs(1));
+        cache.put("key_5".to_string(), "value_6");
+
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(cache.purge_expired(), 1);
+
+        assert_eq!(
+            *removed.lock().unwrap(),
+            vec![
+                ("key_1".to_string(), "value_1", RemovalCause::Replaced),
+                ("key_1".to_string(), "value_2", RemovalCause::Evicted),
+                ("key_3".to_string(), "value_4", RemovalCause::Explicit),
+                ("key_2".to_string(), "value_3", RemovalCause::Expired),
+                ("key_4".to_string(), "value_5", RemovalCause::Expired),
+            ]
+        );
+    }
+
+    #[test]
+    fn listener_receives_expired_entries_replaced_before_removal() {
+        let clock = ManualClock::new();
+        let removed = Removed::default();
+        let mut cache = CacheBuilder::new()
+            .weigher(10, |_: &String, value: &&str| value.len() as u64)
+            .clock(clock.clone())
+            .purge_max_entries(0)
+            .removal_listener(record_removals(&removed))
+            .build();
+
+        cache.put_ttl("key_1".to_string(), "1234", Duration::from_secs(1));
+        cache.put("key_2".to_string(), "1234");
+        clock.advance(Duration::from_secs(2));
+        cache.put("key_1".to_string(), "5678");
+        cache.put("key_2".to_string(), "12345678901");
+
+        assert_eq!(
+            *removed.lock().unwrap(),
+            vec![
+                ("key_1".to_string(), "1234", RemovalCause::Expired),
+                ("key_2".to_string(), "1234", RemovalCause::Replaced),
+            ]
+        );
+    }
+
+    #[test]
+    fn entry_inserts_into_vacant_entries_only() {
+        let mut cache = Cache::default();
+        assert_eq!(
+            cache.entry("test_key".to_string()).or_insert("value_1"),
+            Some(&"value_1")
+        );
+        assert_eq!(
+            cache
+                .entry("test_key".to_string())
+                .or_insert_with(|| unreachable!()),
+            Some(&"value_1")
+        );
+
+        match cache.entry("another_key".to_string()) {
+            entry::Entry::Vacant(entry) => assert_eq!(entry.into_key(), "another_key"),
+            entry::Entry::Occupied(_) => panic!("entry should be vacant"),
+        }
+
+        assert_eq!(cache.entries.len(), 1);
+    }
+
+    #[test]
+    fn entry_treats_expired_items_as_vacant() {
+        let clock = ManualClock::new();
+        let removed = Removed::default();
+        let mut cache = CacheBuilder::new()
+            .clock(clock.clone())
+            .default_ttl(Duration::from_secs(10))
+            .removal_listener(record_removals(&removed))
+            .build();
+
+        cache.put_ttl("test_key".to_string(), "value_1", Duration::from_secs(1));
+        clock.advance(Duration::from_secs(2));
+
+        let entry = cache.entry("test_key".to_string());
+        assert!(matches!(entry, entry::Entry::Vacant(_)));
+        assert_eq!(entry.or_insert("value_2"), Some(&"value_2"));
+
+        assert_eq!(cache.entries.len(), 1);
+        assert_eq!(cache.expirations.len(), 1);
+        assert_eq!(
+            cache.next_expiration(),
+            Some(clock.now() + Duration::from_secs(10))
+        );
+        assert_eq!(
+            *removed.lock().unwrap(),
+            vec![("test_key".to_string(), "value_1", RemovalCause::Expired)]
+        );
+    }
+
+    #[test]
+    fn get_tagged_returns_stale_items_until_purged() {
+        let clock = ManualClock::new();
+        let removed = Removed::default();
+        let mut cache = CacheBuilder::new()
+            .clock(clock.clone())
+            .stale_ttl(Duration::from_secs(5))
+            .removal_listener(record_removals(&removed))
+            .build();
+
+        cache.put_ttl("test_key".to_string(), "value", Duration::from_secs(1));
+        assert_eq!(
+            cache.get_tagged("test_key"),
+            Some((&"value", Freshness::Fresh))
+        );
+        assert_eq!(
+            cache.next_expiration(),
+            Some(clock.now() + Duration::from_secs(6))
+        );
+
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(cache.get("test_key"), None);
+        assert!(!cache.contains_key("test_key"));
+        assert_eq!(
+            cache.get_tagged("test_key"),
+            Some((&"value", Freshness::Stale))
+        );
+
+        cache.purge_expired();
+        assert_eq!(cache.entries.len(), 1);
+
+        clock.advance(Duration::from_secs(4));
+        assert_eq!(cache.get_tagged("test_key"), None);
+        cache.purge_expired();
+        assert_eq!(cache.entries.len(), 0);
+        assert_eq!(cache.expirations.len(), 0);
+        assert_eq!(
+            *removed.lock().unwrap(),
+            vec![("test_key".to_string(), "value", RemovalCause::Expired)]
+        );
+    }
+
+    #[test]
+    fn put_stale_sets_explicit_deadlines() {
+        let clock = ManualClock::new();
+        let mut cache = CacheBuilder::new().clock(clock.clone()).build();
+
+        let now = clock.now();
+        cache.put_stale(
+            "test_key_1".to_string(),
+            "value_1",
+            Some(now + Duration::from_secs(1)),
+            Some(now + Duration::from_secs(3)),
+        );
+        cache.put_stale(
+            "test_key_2".to_string(),
+            "value_2",
+            Some(now + Duration::from_secs(1)),
+            None,
+        );
+        assert_eq!(cache.expirations.len(), 1);
+
+        clock.advance(Duration::from_secs(2));
+        cache.purge_expired();
+        assert_eq!(
+            cache.get_tagged("test_key_1"),
+            Some((&"value_1", Freshness::Stale))
+        );
+        assert_eq!(
+            cache.get_tagged("test_key_2"),
+            Some((&"value_2", Freshness::Stale))
+        );
+
+        clock.advance(Duration::from_secs(2));
+        cache.purge_expired();
+        assert_eq!(cache.get_tagged("test_key_1"), None);
+        assert_eq!(
+            cache.get_tagged("test_key_2"),
+            Some((&"value_2", Freshness::Stale))
+        );
+        assert_eq!(cache.entries.len(), 1);
+    }
+
+    #[test]
+    fn ttl_jitter_spreads_expiration_times() {
+        let clock = ManualClock::new();
+        let build = |seed| {
+            CacheBuilder::new()
+                .clock(clock.clone())
+                .ttl_jitter(Duration::from_secs(10))
+                .seed(seed)
+                .build()
+        };
+
+        let mut cache = build(7);
+        let expires = clock.now() + Duration::from_secs(60);
+        for key in 0..100 {
+            cache.put_exp(key, "value", Some(expires));
+        }
+
+        let deadlines: BTreeSet<_> = (0..100)
+            .map(|key| match cache.entry(key) {
+                entry::Entry::Occupied(entry) => entry.expiration().unwrap(),
+                entry::Entry::Vacant(_) => panic!("missing entry"),
+            })
+            .collect();
+        assert!(deadlines.len() > 90);
+        assert!(deadlines
+            .iter()
+            .all(|&deadline| deadline <= expires && deadline >= expires - Duration::from_secs(10)));
+
+        let mut other = build(7);
+        for key in 0..100 {
+            other.put_exp(key, "value", Some(expires));
+        }
+        assert_eq!(other.next_expiration(), cache.next_expiration());
+
+        cache.put_ttl(100, "value", Duration::from_secs(1));
+        assert!(cache.next_expiration().unwrap() >= clock.now());
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(cache.get(&100), None);
+    }
+
+    #[test]
+    fn ttl_jitter_percent_scales_with_ttl() {
+        let clock = ManualClock::new();
+        let mut cache = CacheBuilder::new()
+            .clock(clock.clone())
+            .ttl_jitter_percent(10.0)
+            .seed(7)
+            .build();
+
+        for key in 0..100 {
+            cache.put_ttl(key, "value", Duration::from_secs(100));
+        }
+
+        let earliest = cache.next_expiration().unwrap();
+        assert!(earliest >= clock.now() + Duration::from_secs(90));
+        assert!(earliest < clock.now() + Duration::from_secs(91));
+
+        clock.advance(Duration::from_secs(95));
+        cache.purge_expired();
+        assert!((30..70).contains(&cache.len()), "{} items", cache.len());
+
+        clock.advance(Duration::from_secs(5));
+        cache.purge_expired();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "jitter percentage must be finite and non-negative")]
+    fn ttl_jitter_percent_rejects_nan() {
+        let _ = CacheBuilder::<&str, &str>::new().ttl_jitter_percent(f64::NAN);
+    }
+
+    #[test]
+    fn get_early_signals_recompute_near_expiration() {
+        let clock = ManualClock::new();
+        let mut cache = CacheBuilder::new()
+            .clock(clock.clone())
+            .early_expiration(1.0)
+            .seed(42)
+            .build();
+
+        cache.put_ttl("test_key", "value", Duration::from_secs(10));
+        cache.put("test_key_never", "value");
+        clock.advance(Duration::from_secs(9));
+        assert_eq!(cache.get_early("test_key"), Some((&"value", false)));
+
+        cache.record_recompute_time("test_key", Duration::from_secs(1));
+        cache.record_recompute_time("test_key_never", Duration::from_secs(1));
+        let signals = (0..1_000)
+            .filter(|_| cache.get_early("test_key") == Some((&"value", true)))
+            .count();
+        assert!((300..450).contains(&signals), "{} signals", signals);
+        assert_eq!(cache.get_early("test_key_never"), Some((&"value", false)));
+
+        cache.put_ttl("test_key", "value", Duration::from_millis(1));
+        assert_eq!(cache.get_early("test_key"), Some((&"value", true)));
+
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(cache.get_early("test_key"), None);
+    }
+
+    #[test]
+    fn entry_modifies_value_and_weight() {
+        let clock = ManualClock::new();
+        let mut cache = CacheBuilder::new()
+            .weigher(10, |_: &String, value: &String| value.len() as u64)
+            .clock(clock.clone())
+            .build();
+
+        let expires = clock.now() + Duration::from_secs(1);
+        cache.put_exp("key_1".to_string(), "1234".to_string(), Some(expires));
+        cache.put("key_2".to_string(), "1234".to_string());
+
+        let entry = cache
+            .entry("key_1".to_string())
+            .and_modify(|value| value.push_str("56"));
+        match entry {
+            entry::Entry::Occupied(entry) => {
+                assert_eq!(entry.get(), "123456");
+                assert_eq!(entry.expiration(), Some(expires));
+            }
+            entry::Entry::Vacant(_) => panic!("entry should be occupied"),
+        }
+
+        assert_eq!(cache.weight(), 10);
+
+        let entry = cache
+            .entry("key_2".to_string())
+            .and_modify(|value| value.push_str("5678901"));
+        assert!(matches!(entry, entry::Entry::Vacant(_)));
+        assert_eq!(cache.weight(), 6);
+        assert!(!cache.contains("key_2"));
+    }
+
+    #[test]
+    fn entry_reschedules_expiration() {
+        let clock = ManualClock::new();
+        let mut cache = CacheBuilder::new().clock(clock.clone()).build();
+        cache.put_ttl("key_1".to_string(), "value_1", Duration::from_secs(10));
+        cache.put("key_2".to_string(), "value_2");
+
+        if let entry::Entry::Occupied(mut entry) = cache.entry("key_1".to_string()) {
+            entry.set_expiration(Some(clock.now() + Duration::from_secs(1)));
+        }
+
+        if let entry::Entry::Occupied(mut entry) = cache.entry("key_2".to_string()) {
+            entry.set_expiration(Some(clock.now() + Duration::from_secs(5)));
+        }
+
+        assert_eq!(cache.expirations.len(), 2);
+        assert_eq!(
+            cache.next_expiration(),
+            Some(clock.now() + Duration::from_secs(1))
+        );
+
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(cache.purge_expired(), 1);
+        assert!(!cache.contains("key_1"));
+
+        if let entry::Entry::Occupied(mut entry) = cache.entry("key_2".to_string()) {
+            entry.set_expiration(None);
+        }
+
+        assert_eq!(cache.expirations.len(), 0);
+    }
+
+    #[test]
+    fn compute_performs_returned_operation() {
+        let removed = Removed::default();
+        let mut cache = CacheBuilder::new()
+            .removal_listener(record_removals(&removed))
+            .build();
+
+        assert_eq!(cache.compute("test_key".to_string(), |_| Op::Nop), None);
+        assert_eq!(
+            cache.compute("test_key".to_string(), |_| Op::Put("value_1")),
+            Some(&"value_1")
+        );
+        assert_eq!(
+            cache.compute("test_key".to_string(), |value| {
+                assert_eq!(value, Some(&"value_1"));
+                Op::Nop
+            }),
+            Some(&"value_1")
+        );
+        assert_eq!(cache.compute("test_key".to_string(), |_| Op::Remove), None);
+
+        assert!(cache.is_empty());
+        assert_eq!(
+            *removed.lock().unwrap(),
+            vec![("test_key".to_string(), "value_1", RemovalCause::Explicit)]
+        );
     }
 }
@@ -0,0 +1,80 @@
+//! Sources of the current time used to expire cache entries.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Provides the current time to a cache, which compares it to the expiration
+/// times of its entries.
+///
+/// Caches use the [SystemClock] by default; a [ManualClock] may be configured
+/// using [CacheBuilder::clock](crate::CacheBuilder::clock) in order to control
+/// the passage of time, such as in tests.
+pub trait Clock {
+    /// Returns the current time.
+    fn now(&self) -> Instant;
+}
+
+/// Clock that reports the current system time, as returned by [Instant::now].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Clock that only advances when instructed to, starting at the time of its creation.
+///
+/// Clones share the same time, so a clone kept outside a cache can be used to advance
+/// the time observed by the cache.
+#[derive(Clone, Debug)]
+pub struct ManualClock {
+    start: Instant,
+    elapsed: Arc<AtomicU64>,
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ManualClock {
+    /// Creates a clock whose time is the current system time.
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            elapsed: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Advances the clock (and all its clones) by the given duration.
+    pub fn advance(&self, duration: Duration) {
+        let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+        self.elapsed.fetch_add(nanos, Ordering::Relaxed);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.start + Duration::from_nanos(self.elapsed.load(Ordering::Relaxed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manual_clock_advances_clones() {
+        let clock = ManualClock::new();
+        let start = clock.now();
+        let clone = clock.clone();
+
+        clone.advance(Duration::from_secs(2));
+        assert_eq!(clock.now(), start + Duration::from_secs(2));
+        assert_eq!(clone.now(), clock.now());
+    }
+}
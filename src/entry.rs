@@ -0,0 +1,238 @@
+//! Provides in-place access to the entries of a [Cache], as returned by [Cache::entry].
+
+use std::hash::{BuildHasher, Hash};
+use std::time::{Duration, Instant};
+
+use crate::clock::Clock;
+use crate::listener::RemovalCause;
+use crate::policy::EvictionPolicy;
+use crate::Cache;
+
+/// View into a single entry of a cache, which is either occupied or vacant.
+///
+/// An item that has expired, but has not been removed from the cache yet,
+/// is treated as vacant.
+#[derive(Debug)]
+pub enum Entry<'a, K, V, P, S, C> {
+    /// Entry holding a value that has not expired.
+    Occupied(OccupiedEntry<'a, K, V, P, S, C>),
+    /// Entry holding no value, or an expired one.
+    Vacant(VacantEntry<'a, K, V, P, S, C>),
+}
+
+/// Operation to perform on an entry, as returned by the function passed
+/// to [Cache::compute] given the entry's current value, if any.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Op<V> {
+    /// Leaves the entry unchanged.
+    Nop,
+    /// Stores the given value, as if by [Cache::put].
+    Put(V),
+    /// Stores the given value with the given time-to-live, as if by [Cache::put_ttl].
+    PutTtl(V, Duration),
+    /// Stores the given value with an optional expiration time, as if by [Cache::put_exp].
+    PutExp(V, Option<Instant>),
+    /// Deletes the entry, as if by [Cache::delete].
+    Remove,
+}
+
+/// Entry of a cache holding a value that has not expired.
+#[derive(Debug)]
+pub struct OccupiedEntry<'a, K, V, P, S, C> {
+    cache: &'a mut Cache<K, V, P, S, C>,
+    key: K,
+    hash: u64,
+    slot: usize,
+}
+
+/// Entry of a cache holding no value, or an expired one.
+#[derive(Debug)]
+pub struct VacantEntry<'a, K, V, P, S, C> {
+    cache: &'a mut Cache<K, V, P, S, C>,
+    key: K,
+    hash: u64,
+    slot: Option<usize>,
+}
+
+impl<'a, K, V, P, S, C> Entry<'a, K, V, P, S, C>
+where
+    K: Eq + Hash,
+    P: EvictionPolicy,
+    S: BuildHasher,
+    C: Clock,
+{
+    /// Returns the key of this entry.
+    pub fn key(&self) -> &K {
+        match self {
+            Self::Occupied(entry) => entry.key(),
+            Self::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// Returns the value of this entry if occupied, or else stores the given value,
+    /// as if by [Cache::put], and returns it.
+    ///
+    /// Returns `None` if the stored value was rejected or removed right away
+    /// in order to keep the cache within its bounds.
+    pub fn or_insert(self, value: V) -> Option<&'a V> {
+        self.or_insert_with(|| value)
+    }
+
+    /// Returns the value of this entry if occupied, or else stores the value
+    /// computed by the given function, as if by [Cache::put], and returns it.
+    ///
+    /// Returns `None` if the stored value was rejected or removed right away
+    /// in order to keep the cache within its bounds.
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> Option<&'a V> {
+        match self {
+            Self::Occupied(entry) => Some(entry.into_ref()),
+            Self::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    /// Modifies the value of this entry if occupied, updating its weight and
+    /// counting as an access to it, but leaving its expiration time unchanged.
+    ///
+    /// If the modified entry no longer fits within the cache's bounds,
+    /// it is evicted, and a vacant entry is returned.
+    pub fn and_modify<F: FnOnce(&mut V)>(self, modify: F) -> Self {
+        match self {
+            Self::Occupied(entry) => entry.modify(modify),
+            entry => entry,
+        }
+    }
+}
+
+impl<'a, K, V, P, S, C> OccupiedEntry<'a, K, V, P, S, C>
+where
+    K: Eq + Hash,
+    P: EvictionPolicy,
+    S: BuildHasher,
+    C: Clock,
+{
+    pub(crate) fn new(cache: &'a mut Cache<K, V, P, S, C>, key: K, hash: u64, slot: usize) -> Self {
+        Self {
+            cache,
+            key,
+            hash,
+            slot,
+        }
+    }
+
+    /// Returns the key of this entry, as stored in the cache.
+    pub fn key(&self) -> &K {
+        self.cache.key_at(self.slot)
+    }
+
+    /// Returns the value of this entry.
+    pub fn get(&self) -> &V {
+        self.cache.value_at(self.slot)
+    }
+
+    /// Converts this entry into a reference to its value,
+    /// with the lifetime of the borrow of the cache.
+    pub fn into_ref(self) -> &'a V {
+        self.cache.value_at(self.slot)
+    }
+
+    /// Returns the time at which this entry expires, if any.
+    pub fn expiration(&self) -> Option<Instant> {
+        self.cache.expiration_at(self.slot)
+    }
+
+    /// Replaces the value of this entry, as if by [Cache::put], and returns it.
+    ///
+    /// Returns `None` if the stored value was rejected or removed right away
+    /// in order to keep the cache within its bounds.
+    pub fn insert(self, value: V) -> Option<&'a V> {
+        let slot = self
+            .cache
+            .put_at(self.hash, Some(self.slot), self.key, value)?;
+        Some(self.cache.value_at(slot))
+    }
+
+    /// Replaces the value of this entry with an optional expiration time,
+    /// as if by [Cache::put_exp], and returns it.
+    ///
+    /// Returns `None` if the stored value was rejected or removed right away
+    /// in order to keep the cache within its bounds.
+    pub fn insert_exp(self, value: V, expires: Option<Instant>) -> Option<&'a V> {
+        let slot = self
+            .cache
+            .insert_at(self.hash, Some(self.slot), self.key, value, expires)?;
+        Some(self.cache.value_at(slot))
+    }
+
+    /// Deletes this entry, as if by [Cache::delete].
+    pub fn remove(self) {
+        self.cache.remove_at(self.slot, RemovalCause::Explicit);
+    }
+
+    /// Sets the time at which this entry expires, if any, as if its value were stored
+    /// using [Cache::put_exp]. The entry remains in the cache until it is purged,
+    /// even if the given time has already passed.
+    pub fn set_expiration(&mut self, expires: Option<Instant>) {
+        self.cache.set_expiration_at(self.slot, expires);
+    }
+
+    fn modify<F: FnOnce(&mut V)>(self, modify: F) -> Entry<'a, K, V, P, S, C> {
+        if self.cache.modify_at(self.slot, modify) {
+            Entry::Occupied(self)
+        } else {
+            Entry::Vacant(VacantEntry::new(self.cache, self.key, self.hash, None))
+        }
+    }
+}
+
+impl<'a, K, V, P, S, C> VacantEntry<'a, K, V, P, S, C>
+where
+    K: Eq + Hash,
+    P: EvictionPolicy,
+    S: BuildHasher,
+    C: Clock,
+{
+    pub(crate) fn new(
+        cache: &'a mut Cache<K, V, P, S, C>,
+        key: K,
+        hash: u64,
+        slot: Option<usize>,
+    ) -> Self {
+        Self {
+            cache,
+            key,
+            hash,
+            slot,
+        }
+    }
+
+    /// Returns the key of this entry.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Takes ownership of the key of this entry.
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Stores the given value in this entry, as if by [Cache::put], and returns it.
+    ///
+    /// Returns `None` if the stored value was rejected or removed right away
+    /// in order to keep the cache within its bounds.
+    pub fn insert(self, value: V) -> Option<&'a V> {
+        let slot = self.cache.put_at(self.hash, self.slot, self.key, value)?;
+        Some(self.cache.value_at(slot))
+    }
+
+    /// Stores the given value in this entry with an optional expiration time,
+    /// as if by [Cache::put_exp], and returns it.
+    ///
+    /// Returns `None` if the stored value was rejected or removed right away
+    /// in order to keep the cache within its bounds.
+    pub fn insert_exp(self, value: V, expires: Option<Instant>) -> Option<&'a V> {
+        let slot = self
+            .cache
+            .insert_at(self.hash, self.slot, self.key, value, expires)?;
+        Some(self.cache.value_at(slot))
+    }
+}
@@ -0,0 +1,45 @@
+//! Per-entry expiration policies computing expiration times from cached entries.
+
+use std::fmt;
+use std::time::Duration;
+
+/// Computes the time-to-live of cache entries when they are created,
+/// updated, or retrieved, such as one derived from the value itself
+/// (e.g., the max-age of an HTTP response).
+///
+/// Each hook returns the remaining lifetime of the entry, counted from
+/// the current time, or `None` if the entry never expires. A lifetime too long
+/// to be represented as an expiration time is treated as never expiring.
+///
+/// The hooks are only consulted for entries stored by [Cache::put](crate::Cache::put);
+/// an explicit time-to-live or expiration time takes precedence, including upon retrieval.
+pub trait Expiry<K, V> {
+    /// Returns the time-to-live of a newly stored entry.
+    fn expire_after_create(&self, key: &K, value: &V) -> Option<Duration>;
+
+    /// Returns the time-to-live of an entry whose value has been replaced,
+    /// given the remaining lifetime of the previous value.
+    /// By default, the entry is treated as newly stored.
+    fn expire_after_update(
+        &self,
+        key: &K,
+        value: &V,
+        current: Option<Duration>,
+    ) -> Option<Duration> {
+        let _ = current;
+        self.expire_after_create(key, value)
+    }
+
+    /// Returns the time-to-live of an entry that has been retrieved,
+    /// given its remaining lifetime. By default, the latter is retained.
+    fn expire_after_read(&self, key: &K, value: &V, current: Option<Duration>) -> Option<Duration> {
+        let _ = (key, value);
+        current
+    }
+}
+
+impl<K, V> fmt::Debug for dyn Expiry<K, V> + Send + Sync {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Expiry")
+    }
+}
@@ -0,0 +1,198 @@
+//! Coalescing of concurrent loads of the same key into a single flight.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::sync::{Arc, Condvar, Mutex};
+
+type Error = Arc<dyn Any + Send + Sync>;
+
+/// Result of a flight, shared with all callers waiting for it.
+#[derive(Clone)]
+pub(crate) enum Outcome<V> {
+    /// The value was loaded.
+    Loaded(V),
+    /// The loader failed with the given error.
+    Failed(Error),
+    /// The load was abandoned before producing a result, such as when it panicked.
+    Abandoned,
+}
+
+/// Means of waking up the callers waiting for a flight once it lands.
+pub(crate) trait Waiters: Default {
+    /// Wakes up all waiting callers.
+    fn wake_all(&self);
+}
+
+impl Waiters for Condvar {
+    fn wake_all(&self) {
+        self.notify_all();
+    }
+}
+
+/// Load of a single key, which other callers may wait for.
+pub(crate) struct Flight<V, W = Condvar> {
+    outcome: Mutex<Option<Outcome<V>>>,
+    waiters: W,
+}
+
+#[cfg(feature = "tokio")]
+impl<V, W> Flight<V, W> {
+    /// Returns the outcome of the flight, if landed.
+    pub(crate) fn outcome(&self) -> Option<Outcome<V>>
+    where
+        V: Clone,
+    {
+        self.outcome
+            .lock()
+            .expect("failed to acquire flight lock")
+            .clone()
+    }
+
+    /// Returns the means of waking up the callers waiting for the flight.
+    pub(crate) fn waiters(&self) -> &W {
+        &self.waiters
+    }
+}
+
+impl<V: Clone> Flight<V> {
+    /// Blocks until the flight lands, and returns its outcome.
+    pub(crate) fn wait(&self) -> Outcome<V> {
+        let mut outcome = self.outcome.lock().expect("failed to acquire flight lock");
+        loop {
+            if let Some(outcome) = &*outcome {
+                return outcome.clone();
+            }
+
+            outcome = self
+                .waiters
+                .wait(outcome)
+                .expect("failed to acquire flight lock");
+        }
+    }
+}
+
+/// Flights in progress, by key.
+pub(crate) struct Flights<K, V, W = Condvar> {
+    flights: Mutex<HashMap<K, Arc<Flight<V, W>>>>,
+}
+
+impl<K, V, W> Default for Flights<K, V, W> {
+    fn default() -> Self {
+        Self {
+            flights: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<K, V, W> fmt::Debug for Flights<K, V, W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flights = self.flights.lock().map_or(0, |flights| flights.len());
+        f.debug_struct("Flights")
+            .field("flights", &flights)
+            .finish()
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+impl<K, V, W> Flights<K, V, W> {
+    /// Returns whether no flight is in progress.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.flights
+            .lock()
+            .expect("failed to acquire flights lock")
+            .is_empty()
+    }
+}
+
+/// Way in which a caller takes part in loading a key.
+pub(crate) enum Takeoff<K: Eq + Hash, V, W: Waiters = Condvar> {
+    /// The value was cached by the time the caller got to load it.
+    Cached(V),
+    /// Another caller is loading the key; the caller should wait for it.
+    Joined(Arc<Flight<V, W>>),
+    /// The caller should load the key, then land the flight.
+    Leading(Landing<K, V, W>),
+}
+
+impl<K: Eq + Hash + Clone, V, W: Waiters> Flights<K, V, W> {
+    /// Joins the flight loading the given key, if any. Otherwise, unless `cached`
+    /// returns a value, starts a flight that the caller is expected to land.
+    ///
+    /// Since `cached` is called while no flight can land, a value stored by
+    /// the previous flight is never loaded again.
+    pub(crate) fn take_off<F>(self: &Arc<Self>, key: &K, cached: F) -> Takeoff<K, V, W>
+    where
+        F: FnOnce() -> Option<V>,
+    {
+        let mut flights = self.flights.lock().expect("failed to acquire flights lock");
+        if let Some(flight) = flights.get(key) {
+            return Takeoff::Joined(flight.clone());
+        }
+
+        if let Some(value) = cached() {
+            return Takeoff::Cached(value);
+        }
+
+        let flight = Arc::new(Flight {
+            outcome: Mutex::new(None),
+            waiters: W::default(),
+        });
+
+        flights.insert(key.clone(), flight.clone());
+        Takeoff::Leading(Landing {
+            flights: self.clone(),
+            key: key.clone(),
+            flight,
+        })
+    }
+}
+
+/// Obligation of the loading caller to land its flight, which is abandoned
+/// if the landing is dropped beforehand, such as when the caller panics.
+pub(crate) struct Landing<K: Eq + Hash, V, W: Waiters = Condvar> {
+    flights: Arc<Flights<K, V, W>>,
+    key: K,
+    flight: Arc<Flight<V, W>>,
+}
+
+impl<K: Eq + Hash, V, W: Waiters> Landing<K, V, W> {
+    /// Returns the flight to be landed.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn flight(&self) -> &Arc<Flight<V, W>> {
+        &self.flight
+    }
+
+    /// Lands the flight with the given outcome, waking up all waiting callers.
+    pub(crate) fn land(self, outcome: Outcome<V>) {
+        self.complete(outcome);
+    }
+
+    /// Removes the flight, unless already landed, so that subsequent loads start a new one,
+    /// and sets its outcome.
+    fn complete(&self, outcome: Outcome<V>) {
+        if let Ok(mut flights) = self.flights.flights.lock() {
+            if flights
+                .get(&self.key)
+                .map_or(false, |flight| Arc::ptr_eq(flight, &self.flight))
+            {
+                flights.remove(&self.key);
+            }
+        }
+
+        if let Ok(mut current) = self.flight.outcome.lock() {
+            if current.is_none() {
+                *current = Some(outcome);
+            }
+        }
+
+        self.flight.waiters.wake_all();
+    }
+}
+
+impl<K: Eq + Hash, V, W: Waiters> Drop for Landing<K, V, W> {
+    fn drop(&mut self) {
+        self.complete(Outcome::Abandoned);
+    }
+}
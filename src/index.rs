@@ -0,0 +1,130 @@
+//! Hash index locating slots by the hashes of their keys.
+
+use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hasher};
+
+const NIL: usize = usize::MAX;
+
+/// Maps key hashes to the slots of the entries holding those keys.
+///
+/// Slots whose keys share a hash are chained together, so the index itself
+/// never compares keys; callers walk the chain of a hash to find the slot
+/// whose key matches. Since the hashes are already computed, the underlying
+/// map does not hash them again.
+#[derive(Debug, Default)]
+pub(crate) struct Index {
+    heads: HashMap<u64, usize, BuildHasherDefault<IdentityHasher>>,
+    next: Vec<usize>,
+}
+
+impl Index {
+    /// Adds the slot to the chain of the given hash.
+    pub(crate) fn insert(&mut self, hash: u64, slot: usize) {
+        if slot >= self.next.len() {
+            self.next.resize(slot + 1, NIL);
+        }
+
+        self.next[slot] = self.heads.insert(hash, slot).unwrap_or(NIL);
+    }
+
+    /// Removes the slot from the chain of the given hash.
+    pub(crate) fn remove(&mut self, hash: u64, slot: usize) {
+        let head = match self.heads.get_mut(&hash) {
+            Some(head) => head,
+            None => return,
+        };
+
+        let next = std::mem::replace(&mut self.next[slot], NIL);
+        if *head == slot {
+            if next == NIL {
+                self.heads.remove(&hash);
+            } else {
+                *head = next;
+            }
+
+            return;
+        }
+
+        let mut prev = *head;
+        while prev != NIL {
+            if self.next[prev] == slot {
+                self.next[prev] = next;
+                return;
+            }
+
+            prev = self.next[prev];
+        }
+    }
+
+    /// Returns the slots indexed under the given hash.
+    pub(crate) fn chain(&self, hash: u64) -> Chain<'_> {
+        Chain {
+            next: &self.next,
+            slot: self.heads.get(&hash).copied().unwrap_or(NIL),
+        }
+    }
+}
+
+/// Iterator over the slots indexed under one hash.
+pub(crate) struct Chain<'a> {
+    next: &'a [usize],
+    slot: usize,
+}
+
+impl Iterator for Chain<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.slot == NIL {
+            return None;
+        }
+
+        let slot = self.slot;
+        self.slot = self.next[slot];
+        Some(slot)
+    }
+}
+
+/// Hasher passing through precomputed 64-bit hashes.
+#[derive(Debug, Default)]
+pub(crate) struct IdentityHasher(u64);
+
+impl Hasher for IdentityHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 << 8) | u64::from(byte);
+        }
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.0 = value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chains_colliding_slots() {
+        let mut index = Index::default();
+        index.insert(7, 0);
+        index.insert(7, 1);
+        index.insert(7, 2);
+        index.insert(8, 3);
+
+        assert_eq!(index.chain(7).collect::<Vec<_>>(), vec![2, 1, 0]);
+
+        index.remove(7, 1);
+        assert_eq!(index.chain(7).collect::<Vec<_>>(), vec![2, 0]);
+
+        index.remove(7, 2);
+        index.remove(7, 0);
+        assert_eq!(index.chain(7).next(), None);
+        assert_eq!(index.chain(8).collect::<Vec<_>>(), vec![3]);
+    }
+}
@@ -3,11 +3,34 @@
 //! The main type provided by this library is [Cache], which supports
 //! the ability to store and retrieve arbitrary key/value pairs. Optionally,
 //! cache entries may be set to expire at a certain time in the future.
+//! Entries may also be inspected and updated in place using [Cache::entry],
+//! which treats expired entries as vacant.
 //!
-//! The implementation offers fast and stable lookup latency, as the cache is
-//! backed by the standard [std::collections::HashMap] implementation. Other than comparing
-//! the item's expiration time to the current system time, no additional computation
-//! is performed during retrieval.
+//! # Storage
+//!
+//! The implementation offers fast and stable lookup latency. Entries are stored in a slab,
+//! a vector whose slots are recycled as entries are removed, and located through a hash index
+//! that maps the hash of each key to the slots holding it. Other than comparing the item's
+//! expiration time to the current time, retrieval from an unbounded cache performs no
+//! additional computation.
+//!
+//! # Eviction policies
+//!
+//! A cache may optionally be bounded to a maximum number of entries, or to a maximum
+//! total weight of entries as computed by a user-supplied [Weigher]. When an insertion
+//! would exceed the bound, expired items are removed first, followed by
+//! items selected by a pluggable [policy::EvictionPolicy]. Implementations of
+//! LRU (the default), LFU, FIFO, CLOCK, SIEVE, W-TinyLFU, and ARC are provided in [policy].
+//!
+//! A bounded cache records each retrieval with its eviction policy, which is guarded
+//! by a mutex so that retrieval only requires shared access to the cache;
+//! concurrent readers of a bounded [SyncCache] thus contend on that mutex.
+//!
+//! In order to release resources held by cached values, a [RemovalListener] may be notified
+//! of each item leaving the cache, along with whether it expired, was evicted, replaced,
+//! or explicitly deleted.
+//!
+//! # Expiration
 //!
 //! In order to limit memory usage to a minimum when items with expiration are cached,
 //! the implementation removes expired items whenever new items are inserted
@@ -15,15 +38,78 @@
 //! a [std::collections::BTreeSet]; when replacing existing items with expiration times,
 //! old entries are first removed from the set. New entries are then inserted according
 //! to their expiration time (if any). Finally, items that expired before the current system
-//! time are removed from the set as well as the backing hash map.
+//! time are removed from the set as well as from the slab and the hash index.
+//! Alternatively, expiring items may be tracked using a hierarchical timing wheel
+//! (see [CacheBuilder::timing_wheel]), which trades exact purge times for constant-time tracking.
+//! Optionally, a [SyncCache] may start a background thread that removes expired items
+//! as soon as they expire (see [SyncCache::with_reaper]), rather than waiting for the next write.
+//!
+//! Instead of an explicit expiration time, items may be stored with a time-to-live,
+//! or with the default time-to-live of a cache configured using [CacheBuilder].
+//! Such a cache may also expire items that have not been retrieved for a given time (time-to-idle),
+//! or compute the time-to-live of each item from the item itself using an [Expiry].
+//! The current time is obtained from a [Clock]; besides the system clock, a manually
+//! advanced clock is provided in [clock] so that expiration can be tested deterministically.
+//!
+//! To spread out the recomputation of items expiring at the same time, a cache may signal
+//! that an item should be recomputed early, with a probability that grows as the item approaches
+//! its expiration time (see [CacheBuilder::early_expiration]). Likewise, the expiration times
+//! of stored items may be jittered (see [CacheBuilder::ttl_jitter]), so that items stored
+//! with the same expiration time do not all expire at once.
+//!
+//! # Concurrency and loading
 //!
 //! To facilitate its use in multi-threaded environments, [SyncCache] wraps an instance of
 //! [Cache] and provides synchronized concurrent access through a standard [std::sync::RwLock].
 //! As a result, multiple threads can concurrently retrieve cached items, while threads
 //! trying to insert, update, or delete cached items must wait for exclusive access.
+//! Read-modify-write operations, such as [SyncCache::compute], are performed under a single
+//! acquisition of the exclusive lock, and thus atomically.
+//! Concurrent misses of the same key may also be coalesced into a single load
+//! using [SyncCache::get_or_load], preventing cache stampedes.
+//!
+//! Building on this, a [LoadingCache] transparently loads missing or expired values
+//! using a [CacheLoader], which may also load several values at once. Values may be refreshed
+//! in the background once they reach a given age, while their previous values keep being served.
+//! Expired values may also be kept for a while as stale (see [CacheBuilder::stale_ttl]),
+//! to be served while they are refreshed or when loading them fails.
+//!
+//! # Async
+//!
+//! With the `tokio` feature enabled, `AsyncCache` wraps an instance of [Cache] in Tokio's
+//! `RwLock`, so that async tasks await the lock rather than block their thread.
+//! Concurrent misses of the same key await a single in-flight future,
+//! without holding the lock while awaiting it.
 
+#[cfg(feature = "tokio")]
+pub mod asynchronous;
+pub mod builder;
 pub mod cache;
+pub mod clock;
+pub mod entry;
+pub mod expiry;
+mod flight;
+mod index;
+mod list;
+pub mod listener;
+pub mod loading;
+pub mod policy;
+mod reaper;
+mod refresh;
+mod rng;
+mod slab;
 pub mod sync;
+pub mod weigher;
+mod wheel;
 
-pub use cache::Cache;
+#[cfg(feature = "tokio")]
+pub use asynchronous::AsyncCache;
+pub use builder::CacheBuilder;
+pub use cache::{Cache, Freshness};
+pub use clock::Clock;
+pub use expiry::Expiry;
+pub use listener::{RemovalCause, RemovalListener};
+pub use loading::{CacheLoader, LoadingCache};
+pub use policy::EvictionPolicy;
 pub use sync::SyncCache;
+pub use weigher::Weigher;
@@ -0,0 +1,148 @@
+//! Doubly-linked lists of slot indices.
+
+const NIL: usize = usize::MAX;
+
+#[derive(Clone, Copy, Debug)]
+struct Node {
+    list: usize,
+    prev: usize,
+    next: usize,
+}
+
+const UNLINKED: Node = Node {
+    list: NIL,
+    prev: NIL,
+    next: NIL,
+};
+
+#[derive(Clone, Copy, Debug)]
+struct Ends {
+    head: usize,
+    tail: usize,
+    len: usize,
+}
+
+const EMPTY: Ends = Ends {
+    head: NIL,
+    tail: NIL,
+    len: 0,
+};
+
+/// A number of doubly-linked lists sharing one link table.
+///
+/// Links are indexed by slot, so each slot belongs to at most one of the
+/// lists at any time. Insertion, removal, and reordering are all O(1) and
+/// do not allocate once the link table has grown to the largest slot.
+#[derive(Debug)]
+pub(crate) struct Lists {
+    nodes: Vec<Node>,
+    ends: Vec<Ends>,
+}
+
+impl Lists {
+    /// Creates the given number of empty lists.
+    pub(crate) fn new(count: usize) -> Self {
+        Self {
+            nodes: Vec::new(),
+            ends: vec![EMPTY; count],
+        }
+    }
+
+    /// Adds another empty list and returns its index.
+    pub(crate) fn add(&mut self) -> usize {
+        self.ends.push(EMPTY);
+        self.ends.len() - 1
+    }
+
+    /// Returns the number of slots in the given list.
+    pub(crate) fn len(&self, list: usize) -> usize {
+        self.ends[list].len
+    }
+
+    /// Returns the slot at the front (oldest end) of the given list.
+    pub(crate) fn front(&self, list: usize) -> Option<usize> {
+        link(self.ends[list].head)
+    }
+
+    /// Returns the slot following the given one (towards the back) in its list.
+    pub(crate) fn next(&self, slot: usize) -> Option<usize> {
+        self.nodes.get(slot).and_then(|node| link(node.next))
+    }
+
+    /// Returns the list containing the given slot, if any.
+    pub(crate) fn list_of(&self, slot: usize) -> Option<usize> {
+        self.nodes.get(slot).and_then(|node| link(node.list))
+    }
+
+    /// Appends the slot to the back (newest end) of the given list.
+    /// The slot must not currently be linked into any list.
+    pub(crate) fn push_back(&mut self, list: usize, slot: usize) {
+        if slot >= self.nodes.len() {
+            self.nodes.resize(slot + 1, UNLINKED);
+        }
+
+        debug_assert_eq!(self.nodes[slot].list, NIL, "slot is already linked");
+        let ends = &mut self.ends[list];
+        self.nodes[slot] = Node {
+            list,
+            prev: ends.tail,
+            next: NIL,
+        };
+
+        if ends.tail == NIL {
+            ends.head = slot;
+        } else {
+            self.nodes[ends.tail].next = slot;
+        }
+
+        ends.tail = slot;
+        ends.len += 1;
+    }
+
+    /// Unlinks the slot from whichever list contains it, returning that list.
+    pub(crate) fn remove(&mut self, slot: usize) -> Option<usize> {
+        let node = *self.nodes.get(slot)?;
+        if node.list == NIL {
+            return None;
+        }
+
+        let ends = &mut self.ends[node.list];
+        if node.prev == NIL {
+            ends.head = node.next;
+        } else {
+            self.nodes[node.prev].next = node.next;
+        }
+
+        if node.next == NIL {
+            ends.tail = node.prev;
+        } else {
+            self.nodes[node.next].prev = node.prev;
+        }
+
+        ends.len -= 1;
+        self.nodes[slot] = UNLINKED;
+        Some(node.list)
+    }
+
+    /// Removes and returns the slot at the front of the given list.
+    pub(crate) fn pop_front(&mut self, list: usize) -> Option<usize> {
+        let slot = self.front(list)?;
+        self.remove(slot);
+        Some(slot)
+    }
+
+    /// Moves the slot to the back of the given list, unlinking it from
+    /// its current list first.
+    pub(crate) fn move_to_back(&mut self, list: usize, slot: usize) {
+        self.remove(slot);
+        self.push_back(list, slot);
+    }
+}
+
+fn link(index: usize) -> Option<usize> {
+    if index == NIL {
+        None
+    } else {
+        Some(index)
+    }
+}
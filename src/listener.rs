@@ -0,0 +1,106 @@
+//! Listeners notified of entries leaving a cache.
+
+use std::fmt;
+use std::mem;
+use std::sync::Arc;
+
+/// Reason why an entry was removed from a cache.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum RemovalCause {
+    /// The entry's expiration time passed.
+    Expired,
+    /// The entry was evicted to keep the cache within its bounds.
+    Evicted,
+    /// The entry's value was replaced by storing another value for its key.
+    Replaced,
+    /// The entry was deleted by the user.
+    Explicit,
+}
+
+/// Receives the entries removed from a cache, such as in order to release
+/// external resources held by their values.
+///
+/// Any closure taking the key, value, and [RemovalCause] implements this trait.
+///
+/// The key of a replaced entry is the one it was replaced with, which is equal to
+/// the key of the remaining entry. An entry that had already expired is reported
+/// as [RemovalCause::Expired], even if it is replaced or deleted before its
+/// removal by the cache.
+pub trait RemovalListener<K, V> {
+    /// Receives an entry removed from the cache for the given cause.
+    fn on_removal(&self, key: K, value: V, cause: RemovalCause);
+}
+
+impl<K, V, F: Fn(K, V, RemovalCause)> RemovalListener<K, V> for F {
+    fn on_removal(&self, key: K, value: V, cause: RemovalCause) {
+        self(key, value, cause)
+    }
+}
+
+type SharedListener<K, V> = Arc<dyn RemovalListener<K, V> + Send + Sync>;
+
+/// Listener of a cache, along with the removals it has not been notified of yet.
+pub(crate) struct Notifier<K, V> {
+    listener: SharedListener<K, V>,
+    pending: Vec<(K, V, RemovalCause)>,
+}
+
+impl<K, V> Notifier<K, V> {
+    pub(crate) fn new<L>(listener: L) -> Self
+    where
+        L: RemovalListener<K, V> + Send + Sync + 'static,
+    {
+        Self {
+            listener: Arc::new(listener),
+            pending: Vec::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, key: K, value: V, cause: RemovalCause) {
+        self.pending.push((key, value, cause));
+    }
+
+    /// Takes the pending removals, which may then be passed to the listener
+    /// after releasing any lock guarding the cache.
+    pub(crate) fn take(&mut self) -> Removals<K, V> {
+        Removals {
+            listener: Some(self.listener.clone()).filter(|_| !self.pending.is_empty()),
+            pending: mem::take(&mut self.pending),
+        }
+    }
+}
+
+impl<K, V> fmt::Debug for Notifier<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Notifier")
+            .field("pending", &self.pending.len())
+            .finish()
+    }
+}
+
+/// Removals taken from a cache, not yet passed to its listener.
+#[must_use]
+pub(crate) struct Removals<K, V> {
+    listener: Option<SharedListener<K, V>>,
+    pending: Vec<(K, V, RemovalCause)>,
+}
+
+impl<K, V> Default for Removals<K, V> {
+    fn default() -> Self {
+        Self {
+            listener: None,
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl<K, V> Removals<K, V> {
+    /// Passes the removals to the listener, in the order in which they occurred.
+    pub(crate) fn notify(self) {
+        if let Some(listener) = self.listener {
+            for (key, value, cause) in self.pending {
+                listener.on_removal(key, value, cause);
+            }
+        }
+    }
+}
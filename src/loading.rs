@@ -0,0 +1,565 @@
+//! Provides a cache that loads missing values using a [CacheLoader].
+
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::cache::Freshness;
+use crate::clock::{Clock, SystemClock};
+use crate::policy::{EvictionPolicy, Lru};
+use crate::refresh::Refresher;
+use crate::SyncCache;
+
+/// Number of consecutive failed refreshes after which a value expires.
+const MAX_REFRESH_FAILURES: u32 = 3;
+
+/// Loads the values of keys missing from a [LoadingCache], such as from a database.
+pub trait CacheLoader<K, V> {
+    /// Error returned when a value cannot be loaded.
+    type Error;
+
+    /// Loads the value of the given key.
+    fn load(&self, key: &K) -> Result<V, Self::Error>;
+
+    /// Loads the values of the given keys, returning exactly one value per key,
+    /// in the same order; [LoadingCache::get_all] panics otherwise. By default,
+    /// loads each value individually; loaders able to fetch several values
+    /// at once should override this.
+    fn load_all(&self, keys: &[K]) -> Result<Vec<V>, Self::Error> {
+        keys.iter().map(|key| self.load(key)).collect()
+    }
+}
+
+/// Synchronized, thread-safe cache that transparently loads missing or expired
+/// values using a [CacheLoader], then stores them in an underlying [SyncCache].
+///
+/// Concurrent loads of the same key by [LoadingCache::get] are coalesced, as with
+/// [SyncCache::get_or_load]; loader errors are returned to all waiting callers,
+/// but not cached. Clones share the same underlying cache and loader.
+///
+/// Values may also be refreshed in the background once they reach a given age
+/// (see [LoadingCache::refresh_after]), while their previous values keep being served.
+#[derive(Debug)]
+pub struct LoadingCache<K, V, L, P = Lru, S = RandomState, C = SystemClock> {
+    cache: SyncCache<K, V, P, S, C>,
+    loader: Arc<L>,
+    ttl: Option<Duration>,
+    refresher: Option<Arc<Refresher<K>>>,
+}
+
+impl<K, V, L, P, S, C> Clone for LoadingCache<K, V, L, P, S, C> {
+    fn clone(&self) -> Self {
+        Self {
+            cache: self.cache.clone(),
+            loader: self.loader.clone(),
+            ttl: self.ttl,
+            refresher: self.refresher.clone(),
+        }
+    }
+}
+
+impl<K, V, L, P, S, C> LoadingCache<K, V, L, P, S, C> {
+    /// Creates a cache that stores the values loaded by the given loader in the given cache.
+    pub fn new(cache: SyncCache<K, V, P, S, C>, loader: L) -> Self {
+        Self {
+            cache,
+            loader: Arc::new(loader),
+            ttl: None,
+            refresher: None,
+        }
+    }
+
+    /// Sets the time-to-live of loaded values. By default, loaded values are stored
+    /// as if by [SyncCache::put], so they expire as configured for the underlying cache.
+    pub fn load_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Returns the age after which values are refreshed in the background, if configured.
+    pub fn refresh_age(&self) -> Option<Duration> {
+        self.refresher.as_ref().map(|refresher| refresher.after())
+    }
+
+    /// Returns the underlying cache, which may be used to store or delete values directly.
+    pub fn cache(&self) -> &SyncCache<K, V, P, S, C> {
+        &self.cache
+    }
+}
+
+impl<K, V, L, P, S, C> LoadingCache<K, V, L, P, S, C>
+where
+    K: Eq + Hash + Clone + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+    L: CacheLoader<K, V> + Send + Sync + 'static,
+    P: EvictionPolicy + Send + 'static,
+    S: BuildHasher + Send + Sync + 'static,
+    C: Clock + Send + Sync + 'static,
+{
+    /// Starts the given number of threads (at least one), which reload values that were
+    /// stored longer than the given age ago once they are retrieved by [LoadingCache::get],
+    /// which keeps returning the previous value in the meantime. Each key is refreshed
+    /// by at most one thread at a time. The threads stop once the last clone
+    /// of this cache is dropped. Has no effect if threads were already started.
+    ///
+    /// Stale values (see [CacheBuilder::stale_ttl](crate::CacheBuilder::stale_ttl))
+    /// are returned and queued for refresh regardless of their age, as are values
+    /// signaled for early recomputation (see
+    /// [CacheBuilder::early_expiration](crate::CacheBuilder::early_expiration)).
+    ///
+    /// A refreshed value is stored as if loaded by [LoadingCache::get], unless its key
+    /// was deleted, purged, or written in the meantime. If the refresh fails, the previous
+    /// value is kept, and refreshed again upon its next retrieval; once the refresh of
+    /// a value has failed three times in a row, the value expires.
+    pub fn refresh_after(mut self, age: Duration, workers: usize) -> Self {
+        if self.refresher.is_some() {
+            return self;
+        }
+
+        let cache = self.cache.clone();
+        let loader = self.loader.clone();
+        let refresher = Refresher::spawn(age, workers, move |key: K, ttl| {
+            let version = match cache.version_of(&key) {
+                Some(version) => version,
+                None => return,
+            };
+
+            let started = cache.now();
+            let value = match loader.load(&key) {
+                Ok(value) => value,
+                Err(_) => {
+                    cache.write(|cache| {
+                        cache.record_refresh_failure(&key, version, MAX_REFRESH_FAILURES)
+                    });
+                    return;
+                }
+            };
+
+            cache.write(|cache| {
+                // The value was deleted, purged, or written in the meantime.
+                if cache.version_of(&key) != Some(version) {
+                    return;
+                }
+
+                let recompute = cache.clock().now().saturating_duration_since(started);
+                match ttl {
+                    Some(ttl) => cache.put_ttl(key.clone(), value, ttl),
+                    None => cache.put(key.clone(), value),
+                }
+
+                cache.record_recompute_time(&key, recompute);
+            });
+        });
+
+        self.refresher = Some(Arc::new(refresher));
+        self
+    }
+}
+
+impl<K, V, L, P, S, C> LoadingCache<K, V, L, P, S, C>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    L: CacheLoader<K, V>,
+    L::Error: Clone + Send + Sync + 'static,
+    P: EvictionPolicy,
+    S: BuildHasher,
+    C: Clock,
+{
+    /// Returns a clone of the cached value for the given key, if present and not expired,
+    /// or else loads and stores it. If configured with [LoadingCache::refresh_after],
+    /// a value older than the refresh age, or stale, is returned, but also queued for refresh.
+    ///
+    /// If loading fails while a stale value is cached for the key (see
+    /// [CacheBuilder::stale_ttl](crate::CacheBuilder::stale_ttl)),
+    /// the stale value is returned instead of the error.
+    pub fn get(&self, key: K) -> Result<V, L::Error> {
+        if let Some(refresher) = &self.refresher {
+            if let Some((value, age, freshness, early)) = self.cache.get_aged(&key) {
+                if early || freshness == Freshness::Stale || age >= refresher.after() {
+                    refresher.submit(key, self.ttl);
+                }
+
+                return Ok(value);
+            }
+        }
+
+        let loader = &self.loader;
+        self.cache
+            .load(key.clone(), self.ttl, |key| loader.load(key))
+            .or_else(|error| match self.cache.get_tagged(&key) {
+                Some((value, _)) => Ok(value),
+                None => Err(error),
+            })
+    }
+
+    /// Returns clones of the cached values for the given keys, in the same order,
+    /// loading and storing those missing or expired with a single call to
+    /// [CacheLoader::load_all]. Unlike [LoadingCache::get], batch loads are not
+    /// coalesced with concurrent loads of the same keys. The loaded values are stored
+    /// under a single acquisition of the exclusive lock.
+    ///
+    /// # Panics
+    ///
+    /// Panics if [CacheLoader::load_all] returns a different number of values than keys.
+    pub fn get_all<I>(&self, keys: I) -> Result<Vec<V>, L::Error>
+    where
+        I: IntoIterator<Item = K>,
+    {
+        let mut values = Vec::new();
+        let mut missing = Vec::new();
+        for key in keys {
+            let value = self.cache.get(&key);
+            if value.is_none() {
+                missing.push((values.len(), key));
+            }
+
+            values.push(value);
+        }
+
+        if !missing.is_empty() {
+            let keys: Vec<K> = missing.iter().map(|(_, key)| key.clone()).collect();
+            let loaded = self.loader.load_all(&keys)?;
+            assert_eq!(
+                loaded.len(),
+                keys.len(),
+                "loader returned {} values for {} keys",
+                loaded.len(),
+                keys.len()
+            );
+
+            let ttl = self.ttl;
+            self.cache.write(|cache| {
+                for ((index, key), value) in missing.into_iter().zip(loaded) {
+                    match ttl {
+                        Some(ttl) => cache.put_ttl(key, value.clone(), ttl),
+                        None => cache.put(key, value.clone()),
+                    }
+
+                    values[index] = Some(value);
+                }
+            });
+        }
+
+        Ok(values.into_iter().flatten().collect())
+    }
+
+    /// Returns a clone of the cached value for the given key, if present and not expired,
+    /// without loading it otherwise.
+    pub fn get_if_present<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.cache.get(key)
+    }
+
+    /// Deletes any cached value for the given key, so that it is loaded again
+    /// by the next call to [LoadingCache::get].
+    pub fn delete<Q>(&self, key: &Q)
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.cache.delete(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use std::thread;
+    use std::time::Instant;
+
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::CacheBuilder;
+
+    #[derive(Default)]
+    struct Squares {
+        loads: AtomicUsize,
+        batches: AtomicUsize,
+    }
+
+    impl CacheLoader<u64, u64> for Squares {
+        type Error = String;
+
+        fn load(&self, key: &u64) -> Result<u64, String> {
+            self.loads.fetch_add(1, Ordering::SeqCst);
+            if *key == 0 {
+                return Err("zero".to_string());
+            }
+
+            Ok(key * key)
+        }
+
+        fn load_all(&self, keys: &[u64]) -> Result<Vec<u64>, String> {
+            self.batches.fetch_add(1, Ordering::SeqCst);
+            keys.iter().map(|key| self.load(key)).collect()
+        }
+    }
+
+    /// Loads versions of values, counting the loads so far.
+    #[derive(Default)]
+    struct Versions {
+        loads: AtomicUsize,
+        failures: AtomicUsize,
+        failing: AtomicBool,
+        gate: Mutex<()>,
+    }
+
+    impl CacheLoader<u64, u64> for Versions {
+        type Error = ();
+
+        fn load(&self, key: &u64) -> Result<u64, ()> {
+            let _gate = self.gate.lock().unwrap();
+            if self.failing.load(Ordering::SeqCst) {
+                self.failures.fetch_add(1, Ordering::SeqCst);
+                return Err(());
+            }
+
+            let version = self.loads.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(key * 100 + version as u64)
+        }
+    }
+
+    fn wait_until(condition: impl Fn() -> bool) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            if condition() {
+                return true;
+            }
+
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        false
+    }
+
+    #[test]
+    fn get_loads_missing_and_expired_values() {
+        let clock = ManualClock::new();
+        let cache = CacheBuilder::new()
+            .clock(clock.clone())
+            .build_loading(Squares::default())
+            .load_ttl(Duration::from_secs(1));
+
+        assert_eq!(cache.get(3), Ok(9));
+        assert_eq!(cache.get(3), Ok(9));
+        assert_eq!(cache.loader.loads.load(Ordering::SeqCst), 1);
+
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(cache.get_if_present(&3), None);
+        assert_eq!(cache.get(3), Ok(9));
+        assert_eq!(cache.loader.loads.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn get_returns_loader_errors() {
+        let cache = CacheBuilder::new().build_loading(Squares::default());
+        assert_eq!(cache.get(0), Err("zero".to_string()));
+        assert_eq!(cache.get(0), Err("zero".to_string()));
+        assert_eq!(cache.loader.loads.load(Ordering::SeqCst), 2);
+        assert!(cache.cache().is_empty());
+    }
+
+    /// Loads a single value regardless of how many keys are requested.
+    struct Truncating;
+
+    impl CacheLoader<u64, u64> for Truncating {
+        type Error = String;
+
+        fn load(&self, key: &u64) -> Result<u64, String> {
+            Ok(*key)
+        }
+
+        fn load_all(&self, keys: &[u64]) -> Result<Vec<u64>, String> {
+            Ok(keys.iter().take(1).copied().collect())
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "loader returned 1 values for 2 keys")]
+    fn get_all_panics_when_loader_returns_too_few_values() {
+        let cache = CacheBuilder::new().build_loading(Truncating);
+        let _ = cache.get_all(vec![1, 2]);
+    }
+
+    #[test]
+    fn get_all_loads_missing_values_in_one_batch() {
+        let cache = CacheBuilder::new().build_loading(Squares::default());
+        assert_eq!(cache.get(2), Ok(4));
+
+        assert_eq!(cache.get_all(vec![1, 2, 3]), Ok(vec![1, 4, 9]));
+        assert_eq!(cache.loader.batches.load(Ordering::SeqCst), 1);
+        assert_eq!(cache.loader.loads.load(Ordering::SeqCst), 3);
+
+        assert_eq!(cache.get_all(vec![3, 0]), Err("zero".to_string()));
+        assert_eq!(cache.get_if_present(&0), None);
+    }
+
+    #[test]
+    fn get_serves_previous_value_while_refreshing() {
+        let clock = ManualClock::new();
+        let cache = CacheBuilder::new()
+            .clock(clock.clone())
+            .build_loading(Versions::default())
+            .load_ttl(Duration::from_secs(10))
+            .refresh_after(Duration::from_secs(1), 2);
+
+        assert_eq!(cache.get(1), Ok(101));
+        clock.advance(Duration::from_secs(2));
+
+        let gate = cache.loader.gate.lock().unwrap();
+        for _ in 0..10 {
+            assert_eq!(cache.get(1), Ok(101));
+        }
+
+        drop(gate);
+        assert!(wait_until(|| cache.get_if_present(&1) == Some(102)));
+        assert_eq!(cache.get(1), Ok(102));
+        assert_eq!(cache.loader.loads.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            cache.cache().next_expiration(),
+            Some(clock.now() + Duration::from_secs(10))
+        );
+    }
+
+    #[test]
+    fn get_expires_values_that_keep_failing_to_refresh() {
+        let clock = ManualClock::new();
+        let cache = CacheBuilder::new()
+            .clock(clock.clone())
+            .build_loading(Versions::default())
+            .refresh_after(Duration::from_secs(1), 1);
+
+        assert_eq!(cache.get(1), Ok(101));
+        cache.loader.failing.store(true, Ordering::SeqCst);
+        clock.advance(Duration::from_secs(2));
+
+        for failures in 1..MAX_REFRESH_FAILURES as usize {
+            assert_eq!(cache.get(1), Ok(101));
+            assert!(wait_until(
+                || cache.loader.failures.load(Ordering::SeqCst) == failures
+            ));
+        }
+
+        assert!(wait_until(|| {
+            let _ = cache.get(1);
+            cache.get_if_present(&1).is_none()
+        }));
+    }
+
+    #[test]
+    fn refresh_keeps_values_written_in_the_meantime() {
+        let clock = ManualClock::new();
+        let cache = CacheBuilder::new()
+            .clock(clock.clone())
+            .build_loading(Versions::default())
+            .refresh_after(Duration::from_secs(1), 1);
+
+        assert_eq!(cache.get(1), Ok(101));
+        clock.advance(Duration::from_secs(2));
+
+        let gate = cache.loader.gate.lock().unwrap();
+        assert_eq!(cache.get(1), Ok(101));
+        // Lets the refresh thread start loading the value.
+        thread::sleep(Duration::from_millis(50));
+        cache.cache().put(1, 7);
+        drop(gate);
+
+        assert!(wait_until(|| cache.loader.loads.load(Ordering::SeqCst) == 2));
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(cache.get_if_present(&1), Some(7));
+    }
+
+    #[test]
+    fn get_falls_back_to_expiry_when_refresh_fails() {
+        let clock = ManualClock::new();
+        let cache = CacheBuilder::new()
+            .clock(clock.clone())
+            .build_loading(Versions::default())
+            .load_ttl(Duration::from_secs(10))
+            .refresh_after(Duration::from_secs(1), 1);
+
+        assert_eq!(cache.get(1), Ok(101));
+        cache.loader.failing.store(true, Ordering::SeqCst);
+
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(cache.get(1), Ok(101));
+        assert_eq!(cache.get(1), Ok(101));
+
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(cache.get(1), Err(()));
+        assert_eq!(cache.get_if_present(&1), None);
+    }
+
+    #[test]
+    fn get_falls_back_to_stale_value_when_load_fails() {
+        let clock = ManualClock::new();
+        let cache = CacheBuilder::new()
+            .clock(clock.clone())
+            .stale_ttl(Duration::from_secs(10))
+            .build_loading(Versions::default())
+            .load_ttl(Duration::from_secs(1));
+
+        assert_eq!(cache.get(1), Ok(101));
+        cache.loader.failing.store(true, Ordering::SeqCst);
+
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(cache.get_if_present(&1), None);
+        assert_eq!(cache.get(1), Ok(101));
+        assert_eq!(cache.loader.loads.load(Ordering::SeqCst), 1);
+
+        cache.loader.failing.store(false, Ordering::SeqCst);
+        assert_eq!(cache.get(1), Ok(102));
+
+        cache.loader.failing.store(true, Ordering::SeqCst);
+        clock.advance(Duration::from_secs(12));
+        assert_eq!(cache.get(1), Err(()));
+    }
+
+    #[test]
+    fn get_serves_stale_value_while_refreshing() {
+        let clock = ManualClock::new();
+        let cache = CacheBuilder::new()
+            .clock(clock.clone())
+            .stale_ttl(Duration::from_secs(10))
+            .build_loading(Versions::default())
+            .load_ttl(Duration::from_secs(1))
+            .refresh_after(Duration::from_secs(60), 1);
+
+        assert_eq!(cache.get(1), Ok(101));
+        clock.advance(Duration::from_secs(2));
+
+        let gate = cache.loader.gate.lock().unwrap();
+        assert_eq!(cache.get(1), Ok(101));
+        drop(gate);
+
+        assert!(wait_until(|| cache.get_if_present(&1) == Some(102)));
+        assert_eq!(cache.loader.loads.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn get_refreshes_values_signaled_for_early_recomputation() {
+        let clock = ManualClock::new();
+        let cache = CacheBuilder::new()
+            .clock(clock.clone())
+            .early_expiration(1.0)
+            .build_loading(Versions::default())
+            .load_ttl(Duration::from_secs(10))
+            .refresh_after(Duration::from_secs(60), 1);
+
+        assert_eq!(cache.get(1), Ok(101));
+        cache
+            .cache()
+            .record_recompute_time(&1, Duration::from_secs(1));
+
+        clock.advance(Duration::from_secs(10) - Duration::from_micros(1));
+        assert_eq!(cache.get(1), Ok(101));
+        assert!(wait_until(|| cache.get_if_present(&1) == Some(102)));
+    }
+}
@@ -0,0 +1,47 @@
+//! Eviction policies used by bounded caches.
+//!
+//! A bounded [Cache](crate::Cache) delegates the choice of which entry to evict
+//! to an [EvictionPolicy]. The cache identifies its entries by slot indices,
+//! which are reported to the policy as entries are inserted, accessed, and removed.
+//! A slot is only reused for a different entry after it has been removed from the policy.
+//! Upon insertion, the policy also receives a hash of the entry's key, which allows it
+//! to recognize keys across their residencies in the cache.
+
+mod adaptive;
+mod fifo;
+mod lfu;
+mod lru;
+mod second_chance;
+mod sieve;
+mod sketch;
+mod tinylfu;
+
+pub use adaptive::Adaptive;
+pub use fifo::Fifo;
+pub use lfu::Lfu;
+pub use lru::Lru;
+pub use second_chance::SecondChance;
+pub use sieve::Sieve;
+pub use tinylfu::TinyLfu;
+
+/// Strategy for selecting the entries to evict from a bounded cache.
+pub trait EvictionPolicy {
+    /// Informs the policy of the maximum number of entries held by the cache.
+    /// A cache bounded by number of entries calls it once, before any entries are inserted.
+    /// A cache bounded only by weight calls it whenever it becomes full with more entries
+    /// than before, passing the number of entries that fit within its maximum weight.
+    fn set_capacity(&mut self, _max_entries: usize) {}
+
+    /// Records the insertion of a new entry with the given key hash.
+    fn insert(&mut self, slot: usize, hash: u64);
+
+    /// Records an access to an existing entry, either by retrieval or replacement.
+    fn touch(&mut self, slot: usize);
+
+    /// Stops tracking an entry that was removed from the cache.
+    fn remove(&mut self, slot: usize);
+
+    /// Selects the next entry to evict and stops tracking it.
+    /// Returns `None` if no entries are being tracked.
+    fn evict(&mut self) -> Option<usize>;
+}
@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+
+use super::EvictionPolicy;
+use crate::list::Lists;
+use crate::slab::Slab;
+
+const RECENT: usize = 0;
+const FREQUENT: usize = 1;
+
+/// Adaptive Replacement Cache (ARC) eviction policy.
+///
+/// Resident entries are split between a recency list, holding entries accessed
+/// only once since insertion, and a frequency list, holding entries accessed again.
+/// Each list is shadowed by a ghost list that remembers the key hashes of entries
+/// recently evicted from it.
+///
+/// Re-inserting a key found in the recency ghost list grows the target size of
+/// the recency list, while one found in the frequency ghost list shrinks it;
+/// victims are then taken from whichever list exceeds its share. The policy thus
+/// continuously adapts to shifts between recency- and frequency-dominated workloads.
+/// All operations are O(1).
+#[derive(Debug)]
+pub struct Adaptive {
+    lists: Lists,
+    hashes: Vec<u64>,
+    ghosts: Lists,
+    ghost_hashes: Slab<u64>,
+    ghost_index: HashMap<u64, usize>,
+    capacity: usize,
+    target: usize,
+    pending: Option<usize>,
+    pending_frequent_ghost: bool,
+}
+
+impl Default for Adaptive {
+    fn default() -> Self {
+        Self {
+            lists: Lists::new(2),
+            hashes: Vec::new(),
+            ghosts: Lists::new(2),
+            ghost_hashes: Slab::default(),
+            ghost_index: HashMap::new(),
+            capacity: 0,
+            target: 0,
+            pending: None,
+            pending_frequent_ghost: false,
+        }
+    }
+}
+
+impl Adaptive {
+    /// Returns the current target size of the recency list.
+    pub fn target(&self) -> usize {
+        self.target
+    }
+
+    /// Ends the eviction pass of the last insertion, if any, after which the inserted
+    /// entry counts towards its list's share like any other.
+    fn settle(&mut self) {
+        self.pending = None;
+        self.pending_frequent_ghost = false;
+    }
+
+    fn remember(&mut self, list: usize, hash: u64) {
+        if let Some(ghost) = self.ghost_index.get(&hash).copied() {
+            self.forget(ghost);
+        }
+
+        let ghost = self.ghost_hashes.insert(hash);
+        self.ghosts.push_back(list, ghost);
+        self.ghost_index.insert(hash, ghost);
+    }
+
+    fn forget(&mut self, ghost: usize) {
+        self.ghosts.remove(ghost);
+        if let Some(hash) = self.ghost_hashes.remove(ghost) {
+            self.ghost_index.remove(&hash);
+        }
+    }
+
+    fn trim_ghosts(&mut self) {
+        while self.lists.len(RECENT) + self.ghosts.len(RECENT) > self.capacity {
+            match self.ghosts.front(RECENT) {
+                Some(ghost) => self.forget(ghost),
+                None => break,
+            }
+        }
+
+        let resident = self.lists.len(RECENT) + self.lists.len(FREQUENT);
+        while resident + self.ghosts.len(RECENT) + self.ghosts.len(FREQUENT) > 2 * self.capacity {
+            match self
+                .ghosts
+                .front(FREQUENT)
+                .or_else(|| self.ghosts.front(RECENT))
+            {
+                Some(ghost) => self.forget(ghost),
+                None => break,
+            }
+        }
+    }
+}
+
+impl EvictionPolicy for Adaptive {
+    fn set_capacity(&mut self, max_entries: usize) {
+        self.settle();
+        self.capacity = max_entries;
+    }
+
+    fn insert(&mut self, slot: usize, hash: u64) {
+        if slot >= self.hashes.len() {
+            self.hashes.resize(slot + 1, 0);
+        }
+
+        self.hashes[slot] = hash;
+        self.pending = Some(slot);
+        self.pending_frequent_ghost = false;
+
+        let ghost = self.ghost_index.get(&hash).copied();
+        match ghost.and_then(|ghost| self.ghosts.list_of(ghost)) {
+            Some(RECENT) => {
+                let delta = (self.ghosts.len(FREQUENT) / self.ghosts.len(RECENT)).max(1);
+                self.target = (self.target + delta).min(self.capacity);
+                self.lists.push_back(FREQUENT, slot);
+            }
+            Some(_) => {
+                let delta = (self.ghosts.len(RECENT) / self.ghosts.len(FREQUENT)).max(1);
+                self.target = self.target.saturating_sub(delta);
+                self.pending_frequent_ghost = true;
+                self.lists.push_back(FREQUENT, slot);
+            }
+            None => self.lists.push_back(RECENT, slot),
+        }
+
+        if let Some(ghost) = ghost {
+            self.forget(ghost);
+        }
+    }
+
+    fn touch(&mut self, slot: usize) {
+        self.settle();
+        if self.lists.list_of(slot).is_some() {
+            self.lists.move_to_back(FREQUENT, slot);
+        }
+    }
+
+    fn remove(&mut self, slot: usize) {
+        self.settle();
+        self.lists.remove(slot);
+    }
+
+    fn evict(&mut self) -> Option<usize> {
+        // The entry being inserted does not count towards its list's share
+        // until any other event ends the eviction pass of its insertion.
+        let pending_recent = self
+            .pending
+            .map_or(false, |slot| self.lists.list_of(slot) == Some(RECENT));
+        let recent = self.lists.len(RECENT) - usize::from(pending_recent);
+        let from_recent = recent > 0
+            && (recent > self.target || (self.pending_frequent_ghost && recent == self.target));
+
+        let victim = if from_recent {
+            self.lists.front(RECENT)
+        } else {
+            match self.lists.front(FREQUENT) {
+                Some(slot) if Some(slot) != self.pending => Some(slot),
+                other => self.lists.front(RECENT).or(other),
+            }
+        }?;
+
+        if let Some(list) = self.lists.remove(victim) {
+            self.remember(list, self.hashes[victim]);
+            self.trim_ghosts();
+        }
+
+        Some(victim)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adapts_to_recency_ghost_hits() {
+        let mut policy = Adaptive::default();
+        policy.set_capacity(2);
+        policy.insert(0, 10);
+        policy.insert(1, 11);
+        policy.touch(0);
+
+        policy.insert(2, 12);
+        assert_eq!(policy.evict(), Some(1));
+        assert_eq!(policy.target(), 0);
+
+        policy.insert(1, 11);
+        assert_eq!(policy.target(), 1);
+        assert_eq!(policy.evict(), Some(0));
+    }
+
+    #[test]
+    fn adapts_to_frequency_ghost_hits() {
+        let mut policy = Adaptive::default();
+        policy.set_capacity(2);
+        policy.insert(0, 10);
+        policy.insert(1, 11);
+        policy.touch(0);
+        policy.insert(2, 12);
+        assert_eq!(policy.evict(), Some(1));
+        policy.insert(1, 11);
+        assert_eq!(policy.evict(), Some(0));
+        assert_eq!(policy.target(), 1);
+
+        policy.insert(0, 10);
+        assert_eq!(policy.target(), 0);
+        assert_eq!(policy.evict(), Some(2));
+    }
+
+    #[test]
+    fn counts_inserted_entry_once_its_eviction_pass_is_over() {
+        let mut policy = Adaptive::default();
+        policy.set_capacity(2);
+        policy.insert(0, 10);
+        policy.insert(1, 11);
+        policy.touch(0);
+
+        assert_eq!(policy.evict(), Some(1));
+    }
+}
@@ -0,0 +1,54 @@
+use super::EvictionPolicy;
+use crate::list::Lists;
+
+const QUEUE: usize = 0;
+
+/// First-in-first-out eviction policy.
+///
+/// Entries are evicted in insertion order, regardless of how often
+/// they are accessed. All operations are O(1).
+#[derive(Debug)]
+pub struct Fifo {
+    lists: Lists,
+}
+
+impl Default for Fifo {
+    fn default() -> Self {
+        Self {
+            lists: Lists::new(1),
+        }
+    }
+}
+
+impl EvictionPolicy for Fifo {
+    fn insert(&mut self, slot: usize, _hash: u64) {
+        self.lists.push_back(QUEUE, slot);
+    }
+
+    fn touch(&mut self, _slot: usize) {}
+
+    fn remove(&mut self, slot: usize) {
+        self.lists.remove(slot);
+    }
+
+    fn evict(&mut self) -> Option<usize> {
+        self.lists.pop_front(QUEUE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_in_insertion_order() {
+        let mut policy = Fifo::default();
+        policy.insert(0, 0);
+        policy.insert(1, 1);
+        policy.touch(0);
+
+        assert_eq!(policy.evict(), Some(0));
+        assert_eq!(policy.evict(), Some(1));
+        assert_eq!(policy.evict(), None);
+    }
+}
@@ -0,0 +1,178 @@
+use super::EvictionPolicy;
+use crate::list::Lists;
+
+const NIL: usize = usize::MAX;
+
+/// Least-frequently-used eviction policy.
+///
+/// Entries are grouped into buckets of equal access count, which are chained
+/// in ascending count order. The victim is the oldest entry in the bucket with
+/// the lowest count; ties are thus broken in least-recently-used order.
+/// All operations are O(1).
+#[derive(Debug)]
+pub struct Lfu {
+    lists: Lists,
+    buckets: Vec<Bucket>,
+    free_buckets: Vec<usize>,
+    lowest: usize,
+    bucket_of: Vec<usize>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Bucket {
+    count: u64,
+    prev: usize,
+    next: usize,
+}
+
+impl Default for Lfu {
+    fn default() -> Self {
+        Self {
+            lists: Lists::new(0),
+            buckets: Vec::new(),
+            free_buckets: Vec::new(),
+            lowest: NIL,
+            bucket_of: Vec::new(),
+        }
+    }
+}
+
+impl Lfu {
+    /// Creates a bucket for the given count, linked right after `prev`
+    /// (or at the head of the chain, if `prev` is `NIL`).
+    fn add_bucket(&mut self, count: u64, prev: usize) -> usize {
+        let next = if prev == NIL {
+            self.lowest
+        } else {
+            self.buckets[prev].next
+        };
+
+        let bucket = Bucket { count, prev, next };
+        let index = match self.free_buckets.pop() {
+            Some(index) => {
+                self.buckets[index] = bucket;
+                index
+            }
+            None => {
+                self.buckets.push(bucket);
+                self.lists.add()
+            }
+        };
+
+        if prev == NIL {
+            self.lowest = index;
+        } else {
+            self.buckets[prev].next = index;
+        }
+
+        if next != NIL {
+            self.buckets[next].prev = index;
+        }
+
+        index
+    }
+
+    /// Unlinks the bucket from the chain if it no longer holds any entries.
+    fn release_bucket(&mut self, index: usize) {
+        if self.lists.len(index) > 0 {
+            return;
+        }
+
+        let Bucket { prev, next, .. } = self.buckets[index];
+        if prev == NIL {
+            self.lowest = next;
+        } else {
+            self.buckets[prev].next = next;
+        }
+
+        if next != NIL {
+            self.buckets[next].prev = prev;
+        }
+
+        self.free_buckets.push(index);
+    }
+}
+
+impl EvictionPolicy for Lfu {
+    fn insert(&mut self, slot: usize, _hash: u64) {
+        let bucket = if self.lowest != NIL && self.buckets[self.lowest].count == 1 {
+            self.lowest
+        } else {
+            self.add_bucket(1, NIL)
+        };
+
+        if slot >= self.bucket_of.len() {
+            self.bucket_of.resize(slot + 1, NIL);
+        }
+
+        self.bucket_of[slot] = bucket;
+        self.lists.push_back(bucket, slot);
+    }
+
+    fn touch(&mut self, slot: usize) {
+        let bucket = self.bucket_of[slot];
+        let Bucket { count, next, .. } = self.buckets[bucket];
+        let target = if next != NIL && self.buckets[next].count == count + 1 {
+            next
+        } else {
+            self.add_bucket(count + 1, bucket)
+        };
+
+        self.lists.move_to_back(target, slot);
+        self.bucket_of[slot] = target;
+        self.release_bucket(bucket);
+    }
+
+    fn remove(&mut self, slot: usize) {
+        if let Some(bucket) = self.lists.remove(slot) {
+            self.release_bucket(bucket);
+        }
+    }
+
+    fn evict(&mut self) -> Option<usize> {
+        if self.lowest == NIL {
+            return None;
+        }
+
+        let bucket = self.lowest;
+        let slot = self.lists.pop_front(bucket);
+        self.release_bucket(bucket);
+        slot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_frequently_used() {
+        let mut policy = Lfu::default();
+        policy.insert(0, 0);
+        policy.insert(1, 1);
+        policy.insert(2, 2);
+        policy.touch(0);
+        policy.touch(0);
+        policy.touch(2);
+
+        assert_eq!(policy.evict(), Some(1));
+        assert_eq!(policy.evict(), Some(2));
+        assert_eq!(policy.evict(), Some(0));
+        assert_eq!(policy.evict(), None);
+    }
+
+    #[test]
+    fn breaks_ties_by_recency() {
+        let mut policy = Lfu::default();
+        policy.insert(0, 0);
+        policy.insert(1, 1);
+        policy.insert(2, 2);
+        policy.touch(1);
+        policy.touch(0);
+        policy.remove(2);
+
+        assert_eq!(policy.evict(), Some(1));
+        assert_eq!(policy.evict(), Some(0));
+        assert_eq!(policy.evict(), None);
+    }
+}
@@ -0,0 +1,59 @@
+use super::EvictionPolicy;
+use crate::list::Lists;
+
+const ORDER: usize = 0;
+
+/// Least-recently-used eviction policy.
+///
+/// Entries are kept in access order; both insertion and retrieval move
+/// an entry to the most-recently-used end, and the victim is taken from
+/// the opposite end. All operations are O(1).
+#[derive(Debug)]
+pub struct Lru {
+    lists: Lists,
+}
+
+impl Default for Lru {
+    fn default() -> Self {
+        Self {
+            lists: Lists::new(1),
+        }
+    }
+}
+
+impl EvictionPolicy for Lru {
+    fn insert(&mut self, slot: usize, _hash: u64) {
+        self.lists.push_back(ORDER, slot);
+    }
+
+    fn touch(&mut self, slot: usize) {
+        self.lists.move_to_back(ORDER, slot);
+    }
+
+    fn remove(&mut self, slot: usize) {
+        self.lists.remove(slot);
+    }
+
+    fn evict(&mut self) -> Option<usize> {
+        self.lists.pop_front(ORDER)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut policy = Lru::default();
+        policy.insert(0, 0);
+        policy.insert(1, 1);
+        policy.insert(2, 2);
+        policy.touch(0);
+
+        assert_eq!(policy.evict(), Some(1));
+        assert_eq!(policy.evict(), Some(2));
+        assert_eq!(policy.evict(), Some(0));
+        assert_eq!(policy.evict(), None);
+    }
+}
@@ -0,0 +1,77 @@
+use super::EvictionPolicy;
+use crate::list::Lists;
+
+const RING: usize = 0;
+
+/// CLOCK (second-chance) eviction policy.
+///
+/// Entries are kept in insertion order with a reference bit that is set
+/// whenever they are accessed. The clock hand sweeps from the oldest entry,
+/// giving referenced entries a second chance by clearing their bit and moving
+/// them behind the hand, until it finds an unreferenced victim.
+/// Accesses are O(1); eviction is amortized O(1).
+#[derive(Debug)]
+pub struct SecondChance {
+    lists: Lists,
+    referenced: Vec<bool>,
+}
+
+impl Default for SecondChance {
+    fn default() -> Self {
+        Self {
+            lists: Lists::new(1),
+            referenced: Vec::new(),
+        }
+    }
+}
+
+impl EvictionPolicy for SecondChance {
+    fn insert(&mut self, slot: usize, _hash: u64) {
+        if slot >= self.referenced.len() {
+            self.referenced.resize(slot + 1, false);
+        }
+
+        self.referenced[slot] = false;
+        self.lists.push_back(RING, slot);
+    }
+
+    fn touch(&mut self, slot: usize) {
+        self.referenced[slot] = true;
+    }
+
+    fn remove(&mut self, slot: usize) {
+        self.lists.remove(slot);
+    }
+
+    fn evict(&mut self) -> Option<usize> {
+        while let Some(slot) = self.lists.pop_front(RING) {
+            if !self.referenced[slot] {
+                return Some(slot);
+            }
+
+            self.referenced[slot] = false;
+            self.lists.push_back(RING, slot);
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gives_referenced_entries_second_chance() {
+        let mut policy = SecondChance::default();
+        policy.insert(0, 0);
+        policy.insert(1, 1);
+        policy.insert(2, 2);
+        policy.touch(0);
+
+        assert_eq!(policy.evict(), Some(1));
+        assert_eq!(policy.evict(), Some(2));
+        assert_eq!(policy.evict(), Some(0));
+        assert_eq!(policy.evict(), None);
+    }
+}
@@ -0,0 +1,93 @@
+use super::EvictionPolicy;
+use crate::list::Lists;
+
+const QUEUE: usize = 0;
+
+/// SIEVE eviction policy.
+///
+/// Entries are kept in insertion order with a visited bit that is set
+/// whenever they are accessed. Unlike [SecondChance](super::SecondChance), retained entries
+/// are not moved; instead, a hand sweeps from the oldest towards the newest entry,
+/// clearing visited bits until it finds an unvisited victim, and resumes
+/// from that position on the next eviction.
+/// Accesses are O(1); eviction is amortized O(1).
+#[derive(Debug)]
+pub struct Sieve {
+    lists: Lists,
+    visited: Vec<bool>,
+    hand: Option<usize>,
+}
+
+impl Default for Sieve {
+    fn default() -> Self {
+        Self {
+            lists: Lists::new(1),
+            visited: Vec::new(),
+            hand: None,
+        }
+    }
+}
+
+impl EvictionPolicy for Sieve {
+    fn insert(&mut self, slot: usize, _hash: u64) {
+        if slot >= self.visited.len() {
+            self.visited.resize(slot + 1, false);
+        }
+
+        self.visited[slot] = false;
+        self.lists.push_back(QUEUE, slot);
+    }
+
+    fn touch(&mut self, slot: usize) {
+        self.visited[slot] = true;
+    }
+
+    fn remove(&mut self, slot: usize) {
+        if self.hand == Some(slot) {
+            self.hand = self.lists.next(slot);
+        }
+
+        self.lists.remove(slot);
+    }
+
+    fn evict(&mut self) -> Option<usize> {
+        let mut slot = self.hand.or_else(|| self.lists.front(QUEUE))?;
+        while self.visited[slot] {
+            self.visited[slot] = false;
+            slot = self
+                .lists
+                .next(slot)
+                .or_else(|| self.lists.front(QUEUE))
+                .unwrap_or(slot);
+        }
+
+        self.hand = self.lists.next(slot);
+        self.lists.remove(slot);
+        Some(slot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retains_visited_entries_in_place() {
+        let mut policy = Sieve::default();
+        policy.insert(0, 0);
+        policy.insert(1, 1);
+        policy.insert(2, 2);
+        policy.touch(0);
+        policy.touch(2);
+
+        assert_eq!(policy.evict(), Some(1));
+
+        policy.insert(3, 3);
+        policy.touch(0);
+
+        assert_eq!(policy.evict(), Some(3));
+        assert_eq!(policy.evict(), Some(2));
+        assert_eq!(policy.evict(), Some(0));
+        assert_eq!(policy.evict(), None);
+    }
+}
@@ -0,0 +1,130 @@
+//! Count-min sketch for estimating key access frequencies.
+
+const DEPTH: usize = 4;
+const MAX_COUNT: u8 = 15;
+/// Maximum number of counters per row, beyond which larger capacities share counters.
+const MAX_WIDTH: usize = 1 << 24;
+const SEEDS: [u64; DEPTH] = [
+    0xc3a5_c85c_97cb_3127,
+    0xb492_b66f_be98_f273,
+    0x9ae1_6a3b_2f90_404f,
+    0xcbf2_9ce4_8422_2325,
+];
+
+/// Approximate frequency counter with periodic aging.
+///
+/// Each key hash maps to one saturating counter in each of several rows;
+/// its estimated frequency is the minimum of those counters. Once the number of
+/// recorded accesses reaches the sample size, all counters are halved, so that
+/// the estimates favor recent history.
+#[derive(Debug, Default)]
+pub(crate) struct CountMinSketch {
+    counters: Vec<u8>,
+    mask: usize,
+    additions: usize,
+    sample_size: usize,
+}
+
+impl CountMinSketch {
+    /// Creates a sketch sized for tracking the given number of keys.
+    pub(crate) fn new(capacity: usize) -> Self {
+        let width = width(capacity);
+        Self {
+            counters: vec![0; width * DEPTH],
+            mask: width - 1,
+            additions: 0,
+            sample_size: capacity.clamp(1, MAX_WIDTH) * 10,
+        }
+    }
+
+    /// Resizes the sketch for tracking the given number of keys, discarding
+    /// the recorded accesses only if the number of counters changes.
+    pub(crate) fn resize(&mut self, capacity: usize) {
+        if width(capacity) == self.mask + 1 {
+            self.sample_size = capacity.clamp(1, MAX_WIDTH) * 10;
+        } else {
+            *self = Self::new(capacity);
+        }
+    }
+
+    /// Returns the estimated number of recorded accesses for the key hash.
+    pub(crate) fn frequency(&self, hash: u64) -> u8 {
+        (0..DEPTH)
+            .map(|row| self.counters[self.index(hash, row)])
+            .min()
+            .unwrap_or_default()
+    }
+
+    /// Records an access for the key hash.
+    pub(crate) fn increment(&mut self, hash: u64) {
+        let mut added = false;
+        for row in 0..DEPTH {
+            let index = self.index(hash, row);
+            if self.counters[index] < MAX_COUNT {
+                self.counters[index] += 1;
+                added = true;
+            }
+        }
+
+        if added {
+            self.additions += 1;
+            if self.additions >= self.sample_size {
+                self.age();
+            }
+        }
+    }
+
+    fn age(&mut self) {
+        for counter in &mut self.counters {
+            *counter /= 2;
+        }
+
+        self.additions /= 2;
+    }
+
+    fn index(&self, hash: u64, row: usize) -> usize {
+        let mixed = (hash ^ SEEDS[row]).wrapping_mul(SEEDS[(row + 1) % DEPTH]);
+        row * (self.mask + 1) + ((mixed >> 32) as usize & self.mask)
+    }
+}
+
+/// Returns the number of counters per row for tracking the given number of keys.
+fn width(capacity: usize) -> usize {
+    capacity.clamp(1, MAX_WIDTH).next_power_of_two()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_frequency() {
+        let mut sketch = CountMinSketch::new(16);
+        for _ in 0..3 {
+            sketch.increment(1);
+        }
+
+        sketch.increment(2);
+
+        assert_eq!(sketch.frequency(1), 3);
+        assert_eq!(sketch.frequency(2), 1);
+    }
+
+    #[test]
+    fn ages_counters() {
+        let mut sketch = CountMinSketch::new(1);
+        for _ in 0..10 {
+            sketch.increment(1);
+        }
+
+        assert_eq!(sketch.frequency(1), 5);
+    }
+    #[test]
+    fn clamps_width_for_huge_capacities() {
+        let mut sketch = CountMinSketch::new(usize::MAX);
+        sketch.increment(1);
+
+        assert_eq!(sketch.frequency(1), 1);
+        assert_eq!(width(usize::MAX), MAX_WIDTH);
+    }
+}
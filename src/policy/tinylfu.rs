@@ -0,0 +1,150 @@
+use super::sketch::CountMinSketch;
+use super::EvictionPolicy;
+use crate::list::Lists;
+
+const WINDOW: usize = 0;
+const PROBATION: usize = 1;
+const PROTECTED: usize = 2;
+
+/// Window TinyLFU (W-TinyLFU) eviction policy.
+///
+/// New entries are admitted into a small LRU window (1% of capacity).
+/// Entries leaving the window become candidates for the main region,
+/// which is a segmented LRU split into probation and protected (80% of the region)
+/// segments; entries accessed while on probation are promoted to the protected segment.
+///
+/// Once the cache is full, a candidate only displaces the main region's victim
+/// if its key has been accessed more frequently. Access frequencies are estimated
+/// by a count-min sketch that is periodically aged, so that it retains
+/// the history of keys no longer in cache without growing with the number of keys.
+/// This makes the policy resistant to scans of rarely used keys.
+#[derive(Debug)]
+pub struct TinyLfu {
+    lists: Lists,
+    sketch: CountMinSketch,
+    hashes: Vec<u64>,
+    window_capacity: usize,
+    main_capacity: usize,
+    protected_capacity: usize,
+}
+
+impl Default for TinyLfu {
+    fn default() -> Self {
+        Self {
+            lists: Lists::new(3),
+            sketch: CountMinSketch::new(0),
+            hashes: Vec::new(),
+            window_capacity: 0,
+            main_capacity: 0,
+            protected_capacity: 0,
+        }
+    }
+}
+
+impl TinyLfu {
+    fn main_len(&self) -> usize {
+        self.lists.len(PROBATION) + self.lists.len(PROTECTED)
+    }
+
+    fn frequency(&self, slot: usize) -> u8 {
+        self.sketch.frequency(self.hashes[slot])
+    }
+}
+
+impl EvictionPolicy for TinyLfu {
+    fn set_capacity(&mut self, max_entries: usize) {
+        self.window_capacity = (max_entries / 100).max(1);
+        self.main_capacity = max_entries.saturating_sub(self.window_capacity);
+        self.protected_capacity = self.main_capacity * 4 / 5;
+        self.sketch.resize(max_entries);
+    }
+
+    fn insert(&mut self, slot: usize, hash: u64) {
+        if slot >= self.hashes.len() {
+            self.hashes.resize(slot + 1, 0);
+        }
+
+        self.hashes[slot] = hash;
+        self.sketch.increment(hash);
+        self.lists.push_back(WINDOW, slot);
+
+        // While the main region has room, candidates are admitted unconditionally.
+        while self.lists.len(WINDOW) > self.window_capacity && self.main_len() < self.main_capacity
+        {
+            if let Some(candidate) = self.lists.pop_front(WINDOW) {
+                self.lists.push_back(PROBATION, candidate);
+            }
+        }
+    }
+
+    fn touch(&mut self, slot: usize) {
+        self.sketch.increment(self.hashes[slot]);
+        match self.lists.list_of(slot) {
+            Some(WINDOW) => self.lists.move_to_back(WINDOW, slot),
+            Some(PROBATION) => {
+                self.lists.move_to_back(PROTECTED, slot);
+                if self.lists.len(PROTECTED) > self.protected_capacity {
+                    if let Some(demoted) = self.lists.pop_front(PROTECTED) {
+                        self.lists.push_back(PROBATION, demoted);
+                    }
+                }
+            }
+            Some(_) => self.lists.move_to_back(PROTECTED, slot),
+            None => {}
+        }
+    }
+
+    fn remove(&mut self, slot: usize) {
+        self.lists.remove(slot);
+    }
+
+    fn evict(&mut self) -> Option<usize> {
+        let victim = self
+            .lists
+            .front(PROBATION)
+            .or_else(|| self.lists.front(PROTECTED));
+
+        if self.lists.len(WINDOW) > self.window_capacity {
+            let candidate = self.lists.front(WINDOW)?;
+            match victim {
+                Some(victim) if self.frequency(candidate) > self.frequency(victim) => {
+                    self.lists.remove(victim);
+                    self.lists.move_to_back(PROBATION, candidate);
+                    Some(victim)
+                }
+                _ => self.lists.pop_front(WINDOW),
+            }
+        } else {
+            let victim = victim.or_else(|| self.lists.front(WINDOW))?;
+            self.lists.remove(victim);
+            Some(victim)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn admits_candidate_only_if_more_frequent() {
+        let mut policy = TinyLfu::default();
+        policy.set_capacity(3);
+        policy.insert(0, 0);
+        policy.insert(1, 1);
+        policy.insert(2, 2);
+        policy.touch(0);
+        policy.touch(0);
+
+        policy.insert(3, 3);
+        assert_eq!(policy.evict(), Some(2));
+
+        policy.touch(3);
+        policy.touch(3);
+        policy.insert(4, 4);
+        assert_eq!(policy.evict(), Some(1));
+
+        policy.insert(5, 5);
+        assert_eq!(policy.evict(), Some(4));
+    }
+}
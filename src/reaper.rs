@@ -0,0 +1,186 @@
+//! Background thread removing expired items from a [SyncCache](crate::SyncCache).
+
+use std::hash::{BuildHasher, Hash};
+use std::sync::{Arc, Condvar, Mutex, RwLock, Weak};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+
+use crate::clock::Clock;
+use crate::policy::EvictionPolicy;
+use crate::Cache;
+
+type LockedCache<K, V, P, S, C> = RwLock<Cache<K, V, P, S, C>>;
+
+#[derive(Debug, Default)]
+struct State {
+    wake_at: Option<Instant>,
+    shutdown: bool,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Signal {
+    state: Mutex<State>,
+    condvar: Condvar,
+}
+
+/// Handle of a background thread that sleeps until the next expiration of a cache
+/// is due, then removes expired items in chunks, acquiring the cache's write lock
+/// for each chunk. The thread stops when the handle is dropped.
+#[derive(Debug)]
+pub(crate) struct Reaper {
+    signal: Arc<Signal>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Reaper {
+    pub(crate) fn spawn<K, V, P, S, C>(cache: &Arc<LockedCache<K, V, P, S, C>>) -> Self
+    where
+        K: Eq + Hash + Send + Sync + 'static,
+        V: Send + Sync + 'static,
+        P: EvictionPolicy + Send + 'static,
+        S: BuildHasher + Send + Sync + 'static,
+        C: Clock + Clone + Send + Sync + 'static,
+    {
+        let signal = Arc::new(Signal::default());
+        let (clock, next) = {
+            let cache = cache.read().expect("failed to acquire read lock");
+            (cache.clock().clone(), cache.next_expiration())
+        };
+
+        signal
+            .state
+            .lock()
+            .expect("failed to acquire reaper lock")
+            .wake_at = next;
+        let thread = {
+            let signal = signal.clone();
+            let cache = Arc::downgrade(cache);
+            thread::Builder::new()
+                .name("qwikache-reaper".to_string())
+                .spawn(move || run(&signal, &cache, &clock))
+                .expect("failed to spawn reaper thread")
+        };
+
+        Self {
+            signal,
+            thread: Some(thread),
+        }
+    }
+
+    /// Wakes the thread earlier if the given expiration time precedes
+    /// the one it is waiting for.
+    pub(crate) fn schedule(&self, next: Option<Instant>) {
+        let next = match next {
+            Some(next) => next,
+            None => return,
+        };
+
+        let mut state = self
+            .signal
+            .state
+            .lock()
+            .expect("failed to acquire reaper lock");
+        if state.wake_at.map_or(true, |wake_at| next < wake_at) {
+            state.wake_at = Some(next);
+            self.signal.condvar.notify_one();
+        }
+    }
+}
+
+#[cfg(test)]
+impl Reaper {
+    /// Returns the signal shared with the thread, which is only released
+    /// once both the handle and the thread are gone.
+    pub(crate) fn signal(&self) -> Weak<Signal> {
+        Arc::downgrade(&self.signal)
+    }
+}
+
+impl Drop for Reaper {
+    fn drop(&mut self) {
+        self.signal
+            .state
+            .lock()
+            .expect("failed to acquire reaper lock")
+            .shutdown = true;
+        self.signal.condvar.notify_one();
+        if let Some(thread) = self.thread.take() {
+            // The last clone of the cache may be dropped by the thread itself, such as when
+            // it removes a value holding that clone; the thread then exits on its own.
+            if thread.thread().id() != thread::current().id() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+fn run<K, V, P, S, C>(signal: &Signal, cache: &Weak<LockedCache<K, V, P, S, C>>, clock: &C)
+where
+    K: Eq + Hash,
+    P: EvictionPolicy,
+    S: BuildHasher,
+    C: Clock,
+{
+    let mut state = signal.state.lock().expect("failed to acquire reaper lock");
+    loop {
+        if state.shutdown {
+            return;
+        }
+
+        let now = clock.now();
+        state = match state.wake_at {
+            Some(wake_at) if wake_at <= now => {
+                state.wake_at = None;
+                drop(state);
+
+                let next = match cache.upgrade() {
+                    Some(cache) => purge(&cache),
+                    None => return,
+                };
+
+                let mut state = signal.state.lock().expect("failed to acquire reaper lock");
+                if let Some(next) = next {
+                    if state.wake_at.map_or(true, |wake_at| next < wake_at) {
+                        state.wake_at = Some(next);
+                    }
+                }
+
+                state
+            }
+            Some(wake_at) => {
+                signal
+                    .condvar
+                    .wait_timeout(state, wake_at - now)
+                    .expect("failed to acquire reaper lock")
+                    .0
+            }
+            None => signal
+                .condvar
+                .wait(state)
+                .expect("failed to acquire reaper lock"),
+        };
+    }
+}
+
+/// Removes all expired items, releasing the write lock between chunks in order to
+/// notify the listener of the removed items, and returns the next expiration time.
+fn purge<K, V, P, S, C>(cache: &LockedCache<K, V, P, S, C>) -> Option<Instant>
+where
+    K: Eq + Hash,
+    P: EvictionPolicy,
+    S: BuildHasher,
+    C: Clock,
+{
+    loop {
+        let (complete, next, removals) = {
+            let mut cache = cache.write().expect("failed to acquire write lock");
+            let complete = cache.purge_chunk();
+            (complete, cache.next_expiration(), cache.take_removals())
+        };
+
+        removals.notify();
+        if complete {
+            return next;
+        }
+    }
+}
@@ -0,0 +1,146 @@
+//! Pool of worker threads refreshing the values of a [LoadingCache](crate::LoadingCache).
+
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::hash::Hash;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+struct State<K> {
+    queue: VecDeque<(K, Option<Duration>)>,
+    pending: HashSet<K>,
+    shutdown: bool,
+}
+
+struct Queue<K> {
+    state: Mutex<State<K>>,
+    available: Condvar,
+}
+
+/// Handle of a pool of threads that refresh the values of submitted keys,
+/// each of which is refreshed by at most one thread at a time.
+/// The threads stop when the handle is dropped, discarding queued keys.
+pub(crate) struct Refresher<K> {
+    after: Duration,
+    queue: Arc<Queue<K>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl<K> Refresher<K>
+where
+    K: Eq + Hash + Clone + Send + 'static,
+{
+    /// Starts the given number of threads (at least one), which refresh values
+    /// that were stored for longer than `after` using the given function,
+    /// passing it the time-to-live of the refreshed value, if any.
+    pub(crate) fn spawn<F>(after: Duration, workers: usize, refresh: F) -> Self
+    where
+        F: Fn(K, Option<Duration>) + Send + Sync + 'static,
+    {
+        let queue = Arc::new(Queue {
+            state: Mutex::new(State {
+                queue: VecDeque::new(),
+                pending: HashSet::new(),
+                shutdown: false,
+            }),
+            available: Condvar::new(),
+        });
+
+        let refresh = Arc::new(refresh);
+        let workers = (0..workers.max(1))
+            .map(|_| {
+                let queue = queue.clone();
+                let refresh = refresh.clone();
+                thread::Builder::new()
+                    .name("qwikache-refresh".to_string())
+                    .spawn(move || run(&queue, &*refresh))
+                    .expect("failed to spawn refresh thread")
+            })
+            .collect();
+
+        Self {
+            after,
+            queue,
+            workers,
+        }
+    }
+}
+
+impl<K> Refresher<K> {
+    /// Returns the age after which values are refreshed.
+    pub(crate) fn after(&self) -> Duration {
+        self.after
+    }
+}
+
+impl<K: Eq + Hash + Clone> Refresher<K> {
+    /// Queues the key for refresh with the given time-to-live, if any,
+    /// unless it is already queued or being refreshed.
+    pub(crate) fn submit(&self, key: K, ttl: Option<Duration>) {
+        let mut state = self
+            .queue
+            .state
+            .lock()
+            .expect("failed to acquire refresh lock");
+        if state.pending.insert(key.clone()) {
+            state.queue.push_back((key, ttl));
+            self.queue.available.notify_one();
+        }
+    }
+}
+
+impl<K> Drop for Refresher<K> {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.queue.state.lock() {
+            state.shutdown = true;
+        }
+
+        self.queue.available.notify_all();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl<K> fmt::Debug for Refresher<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Refresher")
+            .field("after", &self.after)
+            .field("workers", &self.workers.len())
+            .finish()
+    }
+}
+
+fn run<K, F>(queue: &Queue<K>, refresh: &F)
+where
+    K: Eq + Hash + Clone,
+    F: Fn(K, Option<Duration>),
+{
+    let mut state = queue.state.lock().expect("failed to acquire refresh lock");
+    loop {
+        if state.shutdown {
+            return;
+        }
+
+        let (key, ttl) = match state.queue.pop_front() {
+            Some(item) => item,
+            None => {
+                state = queue
+                    .available
+                    .wait(state)
+                    .expect("failed to acquire refresh lock");
+                continue;
+            }
+        };
+
+        drop(state);
+
+        // A panicking loader leaves the value to expire, but does not stop the thread.
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| refresh(key.clone(), ttl)));
+
+        state = queue.state.lock().expect("failed to acquire refresh lock");
+        state.pending.remove(&key);
+    }
+}
@@ -0,0 +1,82 @@
+//! Slot-indexed storage with stable indices.
+
+use std::ops::{Index, IndexMut};
+
+/// Vector-backed storage that hands out stable slot indices.
+///
+/// Removed slots are recycled by subsequent insertions, so the storage
+/// only grows to the maximum number of simultaneously occupied slots.
+#[derive(Debug)]
+pub(crate) struct Slab<T> {
+    entries: Vec<Option<T>>,
+    free: Vec<usize>,
+}
+
+impl<T> Default for Slab<T> {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+}
+
+impl<T> Slab<T> {
+    /// Stores the value in a vacant slot and returns its index.
+    pub(crate) fn insert(&mut self, value: T) -> usize {
+        match self.free.pop() {
+            Some(slot) => {
+                self.entries[slot] = Some(value);
+                slot
+            }
+            None => {
+                self.entries.push(Some(value));
+                self.entries.len() - 1
+            }
+        }
+    }
+
+    /// Returns the value stored in the given slot, if any.
+    pub(crate) fn get(&self, slot: usize) -> Option<&T> {
+        self.entries.get(slot).and_then(Option::as_ref)
+    }
+
+    /// Returns a mutable reference to the value stored in the given slot, if any.
+    pub(crate) fn get_mut(&mut self, slot: usize) -> Option<&mut T> {
+        self.entries.get_mut(slot).and_then(Option::as_mut)
+    }
+
+    /// Returns the number of occupied slots.
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len() - self.free.len()
+    }
+
+    /// Returns an iterator over the stored values.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &T> {
+        self.entries.iter().flatten()
+    }
+
+    /// Removes and returns the value stored in the given slot, if any.
+    pub(crate) fn remove(&mut self, slot: usize) -> Option<T> {
+        let value = self.entries.get_mut(slot).and_then(Option::take);
+        if value.is_some() {
+            self.free.push(slot);
+        }
+
+        value
+    }
+}
+
+impl<T> Index<usize> for Slab<T> {
+    type Output = T;
+
+    fn index(&self, slot: usize) -> &T {
+        self.get(slot).expect("vacant slot")
+    }
+}
+
+impl<T> IndexMut<usize> for Slab<T> {
+    fn index_mut(&mut self, slot: usize) -> &mut T {
+        self.get_mut(slot).expect("vacant slot")
+    }
+}
@@ -1,48 +1,178 @@
 //! Provides a wrapper that supports synchronized access to [Cache]
 //! from multiple concurrent threads.
 
-#[cfg(test)]
-use mock_instant::Instant;
-
-#[cfg(not(test))]
-use std::time::Instant;
+use std::collections::hash_map::RandomState;
 use std::{
-    hash::Hash,
-    sync::{Arc, RwLock},
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+    sync::{Arc, Mutex, MutexGuard, RwLock},
+    time::{Duration, Instant},
 };
 
+use crate::cache::Freshness;
+use crate::clock::{Clock, SystemClock};
+use crate::entry::{Entry, Op};
+use crate::flight::{Flights, Outcome, Takeoff};
+use crate::policy::{EvictionPolicy, Lru};
+use crate::reaper::Reaper;
+use crate::weigher::Weigher;
 use crate::Cache;
 
+type SharedCache<K, V, P, S, C> = Arc<RwLock<Cache<K, V, P, S, C>>>;
+
 /// Synchronized, thread-safe key/value cache that supports multiple
 /// concurrent readers.
-#[derive(Debug, Default)]
-pub struct SyncCache<K, V> {
-    cache: Arc<RwLock<Cache<K, V>>>,
+///
+/// Clones share the same underlying cache. A background thread removing
+/// expired items may be started using [SyncCache::with_reaper]; it stops
+/// once the last clone is dropped.
+///
+/// The removal listener of the cache, if any, is notified after the lock
+/// is released, so it may access the cache itself.
+#[derive(Debug)]
+pub struct SyncCache<K, V, P = Lru, S = RandomState, C = SystemClock> {
+    cache: SharedCache<K, V, P, S, C>,
+    flights: Arc<Flights<K, V>>,
+    reaper: Arc<Mutex<Option<Reaper>>>,
+}
+
+impl<K, V, P, S, C> Clone for SyncCache<K, V, P, S, C> {
+    fn clone(&self) -> Self {
+        Self {
+            cache: self.cache.clone(),
+            flights: self.flights.clone(),
+            reaper: self.reaper.clone(),
+        }
+    }
+}
+
+impl<K, V> Default for SyncCache<K, V> {
+    fn default() -> Self {
+        Cache::default().into()
+    }
+}
+
+impl<K, V> SyncCache<K, V> {
+    /// Creates a cache that holds at most `max_entries` items.
+    /// Once full, storing a new item evicts the least-recently-used one.
+    pub fn with_capacity(max_entries: usize) -> Self {
+        Cache::with_capacity(max_entries).into()
+    }
+
+    /// Creates a cache that holds items up to a total weight of `max_weight`,
+    /// as computed by the given weigher. Once full, storing a new item evicts
+    /// the least-recently-used items until the total weight is within bounds.
+    /// Items heavier than `max_weight` are rejected.
+    pub fn with_weigher<W>(max_weight: u64, weigher: W) -> Self
+    where
+        W: Weigher<K, V> + Send + Sync + 'static,
+    {
+        Cache::with_weigher(max_weight, weigher).into()
+    }
+}
+
+impl<K, V, P: EvictionPolicy> SyncCache<K, V, P> {
+    /// Creates a cache that holds at most `max_entries` items.
+    /// Once full, storing a new item evicts the one selected by the given policy.
+    pub fn with_policy(max_entries: usize, policy: P) -> Self {
+        Cache::with_policy(max_entries, policy).into()
+    }
+}
+
+impl<K, V, P, S, C> From<Cache<K, V, P, S, C>> for SyncCache<K, V, P, S, C> {
+    fn from(mut cache: Cache<K, V, P, S, C>) -> Self {
+        cache.defer_notifications();
+        Self {
+            cache: Arc::new(RwLock::new(cache)),
+            flights: Arc::default(),
+            reaper: Arc::default(),
+        }
+    }
+}
+
+impl<K, V, P, S, C> SyncCache<K, V, P, S, C> {
+    /// Returns the handle of the background thread removing expired items, if started.
+    fn reaper(&self) -> MutexGuard<'_, Option<Reaper>> {
+        self.reaper.lock().expect("failed to acquire reaper lock")
+    }
 }
 
-impl<K: Clone + Eq + Hash + Ord, V: Clone> SyncCache<K, V> {
+impl<K, V, P, S, C> SyncCache<K, V, P, S, C>
+where
+    K: Eq + Hash + Send + Sync + 'static,
+    V: Send + Sync + 'static,
+    P: EvictionPolicy + Send + 'static,
+    S: BuildHasher + Send + Sync + 'static,
+    C: Clock + Clone + Send + Sync + 'static,
+{
+    /// Starts a background thread that sleeps until the next expiration is due,
+    /// then removes expired items in chunks, releasing the write lock between chunks
+    /// so that other threads are not blocked for long. Writes that bring the next
+    /// expiration forward wake the thread up early. The thread stops once the last
+    /// clone of the cache is dropped.
+    ///
+    /// The thread waits in real time, so with a [ManualClock](crate::clock::ManualClock)
+    /// it only observes advances of the clock when it wakes up. Has no effect if
+    /// a thread was already started for any clone of the cache.
+    pub fn with_reaper(self) -> Self {
+        if self.reaper().is_some() {
+            return self;
+        }
+
+        // The thread is spawned without holding the reaper lock, which writes acquire
+        // while holding the write lock that spawning the thread requires.
+        let spawned = Reaper::spawn(&self.cache);
+        let unused = {
+            let mut reaper = self.reaper();
+            match &*reaper {
+                Some(_) => Some(spawned),
+                None => {
+                    *reaper = Some(spawned);
+                    None
+                }
+            }
+        };
+
+        drop(unused);
+        self
+    }
+}
+
+impl<K, V, P, S, C> SyncCache<K, V, P, S, C>
+where
+    K: Eq + Hash,
+    V: Clone,
+    P: EvictionPolicy,
+    S: BuildHasher,
+    C: Clock,
+{
     /// Stores a value for the given key, potentially replacing a previously cached value.
-    /// The entry never expires.
+    /// The entry expires after the time-to-live computed by the cache's [Expiry](crate::Expiry),
+    /// if any, or else after its default time-to-live, if any; otherwise, it never expires.
     /// Blocks until it acquires an exclusive lock.
     pub fn put(&self, key: K, value: V) {
-        self.cache
-            .write()
-            .expect("failed to acquire write lock")
-            .put(key, value);
+        self.write(|cache| cache.put(key, value));
+    }
+
+    /// Stores a value for the given key, expiring after the given time-to-live.
+    /// Blocks until it acquires an exclusive lock.
+    pub fn put_ttl(&self, key: K, value: V, ttl: Duration) {
+        self.write(|cache| cache.put_ttl(key, value, ttl));
     }
 
     /// Stores a value for the given key, with an optional expiration time.
     /// Blocks until it acquires an exclusive lock.
     pub fn put_exp(&self, key: K, value: V, expires: Option<Instant>) {
-        self.cache
-            .write()
-            .expect("failed to acquire write lock")
-            .put_exp(key, value, expires);
+        self.write(|cache| cache.put_exp(key, value, expires));
     }
 
     /// Returns a clone of the cached value for the given key, if present and not expired.
     /// Blocks until it acquires a shared lock.
-    pub fn get(&self, key: &K) -> Option<V> {
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
         self.cache
             .read()
             .expect("failed to acquire read lock")
@@ -50,12 +180,638 @@ impl<K: Clone + Eq + Hash + Ord, V: Clone> SyncCache<K, V> {
             .cloned()
     }
 
+    /// Returns a clone of the cached value for the given key, if present and either
+    /// fresh or stale, along with its freshness; see [Cache::get_tagged].
+    /// Blocks until it acquires a shared lock.
+    pub fn get_tagged<Q>(&self, key: &Q) -> Option<(V, Freshness)>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.cache
+            .read()
+            .expect("failed to acquire read lock")
+            .get_tagged(key)
+            .map(|(value, freshness)| (value.clone(), freshness))
+    }
+
+    /// Returns a clone of the cached value for the given key, if present and not expired,
+    /// along with whether it should be recomputed ahead of its expiration;
+    /// see [Cache::get_early]. Blocks until it acquires a shared lock.
+    pub fn get_early<Q>(&self, key: &Q) -> Option<(V, bool)>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.cache
+            .read()
+            .expect("failed to acquire read lock")
+            .get_early(key)
+            .map(|(value, early)| (value.clone(), early))
+    }
+
+    /// Records the time it took to compute the value cached for the given key, if present;
+    /// see [Cache::record_recompute_time]. Values loaded using [SyncCache::get_or_load]
+    /// have their load time recorded automatically.
+    /// Blocks until it acquires an exclusive lock.
+    pub fn record_recompute_time<Q>(&self, key: &Q, recompute: Duration)
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.write(|cache| cache.record_recompute_time(key, recompute));
+    }
+
+    /// Returns whether a value is cached for the given key and not expired.
+    /// Blocks until it acquires a shared lock.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.cache
+            .read()
+            .expect("failed to acquire read lock")
+            .contains_key(key)
+    }
+
+    /// Removes all expired items, including any left behind by writes
+    /// that exhausted their purge budget.
+    /// Blocks until it acquires an exclusive lock.
+    pub fn run_pending_tasks(&self) {
+        self.write(|cache| cache.run_pending_tasks());
+    }
+
+    /// Removes all expired items and returns how many were removed.
+    /// Blocks until it acquires an exclusive lock.
+    pub fn purge_expired(&self) -> usize {
+        self.write(|cache| cache.purge_expired())
+    }
+
+    /// Returns the time at which the earliest tracked expiration is due, if any.
+    /// Blocks until it acquires a shared lock.
+    pub fn next_expiration(&self) -> Option<Instant> {
+        self.cache
+            .read()
+            .expect("failed to acquire read lock")
+            .next_expiration()
+    }
+
+    /// Returns the number of cached items, including expired items that
+    /// have not been removed yet.
+    /// Blocks until it acquires a shared lock.
+    pub fn len(&self) -> usize {
+        self.cache
+            .read()
+            .expect("failed to acquire read lock")
+            .len()
+    }
+
+    /// Returns whether the cache holds no items, including expired items that
+    /// have not been removed yet.
+    /// Blocks until it acquires a shared lock.
+    pub fn is_empty(&self) -> bool {
+        self.cache
+            .read()
+            .expect("failed to acquire read lock")
+            .is_empty()
+    }
+
+    /// Returns the number of cached items that have not expired.
+    /// Blocks until it acquires a shared lock.
+    pub fn len_unexpired(&self) -> usize {
+        self.cache
+            .read()
+            .expect("failed to acquire read lock")
+            .len_unexpired()
+    }
+
+    /// Returns whether the cache holds no items that have not expired.
+    /// Blocks until it acquires a shared lock.
+    pub fn is_empty_unexpired(&self) -> bool {
+        self.cache
+            .read()
+            .expect("failed to acquire read lock")
+            .is_empty_unexpired()
+    }
+
     /// Deletes any cached value for the given key.
     /// Blocks until it acquires an exclusive lock.
-    pub fn delete(&self, key: &K) {
+    pub fn delete<Q>(&self, key: &Q)
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.write(|cache| cache.delete(key));
+    }
+
+    /// Updates the entry for the given key by performing the operation returned by
+    /// the given function, which is passed the current value, if present and not expired.
+    /// Returns a clone of the resulting value, if any.
+    /// Blocks until it acquires an exclusive lock, which is held while calling the function.
+    pub fn compute<F>(&self, key: K, compute: F) -> Option<V>
+    where
+        F: FnOnce(Option<&V>) -> Op<V>,
+    {
+        self.write(|cache| cache.compute(key, compute).cloned())
+    }
+
+    /// Returns a clone of the cached value for the given key, if present and not expired,
+    /// or else stores the value computed by the given function, as if by [SyncCache::put],
+    /// and returns a clone of it.
+    /// Blocks until it acquires an exclusive lock, which is held while calling the function.
+    pub fn get_or_insert_with<F>(&self, key: K, default: F) -> V
+    where
+        F: FnOnce() -> V,
+    {
+        self.write(|cache| match cache.entry(key) {
+            Entry::Occupied(entry) => entry.get().clone(),
+            Entry::Vacant(entry) => {
+                let value = default();
+                entry.insert(value.clone());
+                value
+            }
+        })
+    }
+
+    /// Replaces the cached value for the given key, if present and not expired,
+    /// with the one computed from it by the given function, as if by [SyncCache::put],
+    /// and returns a clone of the new value.
+    /// Blocks until it acquires an exclusive lock, which is held while calling the function.
+    pub fn update<F>(&self, key: K, update: F) -> Option<V>
+    where
+        F: FnOnce(&V) -> V,
+    {
+        self.compute(key, |value| {
+            value.map_or(Op::Nop, |value| Op::Put(update(value)))
+        })
+    }
+
+    /// Deletes the cached value for the given key, if present and not expired,
+    /// provided it satisfies the given predicate.
+    /// Returns a clone of the value left in the cache, if any.
+    /// Blocks until it acquires an exclusive lock, which is held while calling the function.
+    pub fn remove_if<F>(&self, key: K, predicate: F) -> Option<V>
+    where
+        F: FnOnce(&V) -> bool,
+    {
+        self.compute(key, |value| match value {
+            Some(value) if predicate(value) => Op::Remove,
+            _ => Op::Nop,
+        })
+    }
+
+    /// Replaces the cached value for the given key, if present and not expired,
+    /// provided it satisfies the given predicate, as if by [SyncCache::put].
+    /// Returns a clone of the value left in the cache, if any.
+    /// Blocks until it acquires an exclusive lock, which is held while calling the function.
+    pub fn replace_if<F>(&self, key: K, predicate: F, value: V) -> Option<V>
+    where
+        F: FnOnce(&V) -> bool,
+    {
+        self.compute(key, |current| match current {
+            Some(current) if predicate(current) => Op::Put(value),
+            _ => Op::Nop,
+        })
+    }
+
+    /// Returns a clone of the cached value for the given key, if present and not expired,
+    /// or else loads it using the given function and stores it, as if by [SyncCache::put].
+    ///
+    /// Concurrent calls for the same key are coalesced: while one thread runs its loader,
+    /// the others block until it finishes, and then return the same result, without running
+    /// their own loaders. If the loader fails, its error is returned to all of them,
+    /// and nothing is stored, so the next call runs a loader again. Should the loader panic,
+    /// one of the waiting threads runs its own loader instead.
+    ///
+    /// No lock on the cache is held while running the loader.
+    pub fn get_or_load<F, E>(&self, key: K, load: F) -> Result<V, E>
+    where
+        K: Clone,
+        F: FnOnce(&K) -> Result<V, E>,
+        E: Clone + Send + Sync + 'static,
+    {
+        self.load(key, None, load)
+    }
+
+    /// Implements [SyncCache::get_or_load], storing loaded values with the given
+    /// time-to-live, if any, or else as if by [SyncCache::put].
+    pub(crate) fn load<F, E>(&self, key: K, ttl: Option<Duration>, load: F) -> Result<V, E>
+    where
+        K: Clone,
+        F: FnOnce(&K) -> Result<V, E>,
+        E: Clone + Send + Sync + 'static,
+    {
+        if let Some(value) = self.get(&key) {
+            return Ok(value);
+        }
+
+        loop {
+            let landing = match self.flights.take_off(&key, || self.get(&key)) {
+                Takeoff::Cached(value) => return Ok(value),
+                Takeoff::Leading(landing) => landing,
+                Takeoff::Joined(flight) => match flight.wait() {
+                    Outcome::Loaded(value) => return Ok(value),
+                    Outcome::Failed(error) => match error.downcast_ref::<E>() {
+                        Some(error) => return Err(error.clone()),
+                        // The loader of another call failed with an error of another type.
+                        None => continue,
+                    },
+                    Outcome::Abandoned => continue,
+                },
+            };
+
+            let started = self.now();
+            return match load(&key) {
+                Ok(value) => {
+                    self.store_loaded(key, value.clone(), ttl, started);
+
+                    landing.land(Outcome::Loaded(value.clone()));
+                    Ok(value)
+                }
+                Err(error) => {
+                    landing.land(Outcome::Failed(Arc::new(error.clone())));
+                    Err(error)
+                }
+            };
+        }
+    }
+
+    /// Stores a loaded value with the given time-to-live, if any, or else as if by
+    /// [SyncCache::put], and records the time elapsed since its load started.
+    fn store_loaded(&self, key: K, value: V, ttl: Option<Duration>, started: Instant)
+    where
+        K: Clone,
+    {
+        self.write(|cache| {
+            let recompute = cache.clock().now().saturating_duration_since(started);
+            match ttl {
+                Some(ttl) => cache.put_ttl(key.clone(), value, ttl),
+                None => cache.put(key.clone(), value),
+            }
+
+            cache.record_recompute_time(&key, recompute);
+        });
+    }
+
+    /// Returns the current time according to the cache's clock.
+    pub(crate) fn now(&self) -> Instant {
+        self.cache
+            .read()
+            .expect("failed to acquire read lock")
+            .clock()
+            .now()
+    }
+
+    /// Returns a clone of the cached value for the given key, if present and either
+    /// fresh or stale, along with the time elapsed since it was stored, its freshness,
+    /// and whether it should be recomputed early.
+    /// Blocks until it acquires a shared lock.
+    pub(crate) fn get_aged<Q>(&self, key: &Q) -> Option<(V, Duration, Freshness, bool)>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.cache
+            .read()
+            .expect("failed to acquire read lock")
+            .get_aged(key)
+            .map(|(value, age, freshness, early)| (value.clone(), age, freshness, early))
+    }
+
+    /// Returns the version of the value cached for the given key, if present and either
+    /// fresh or stale, without counting as an access to it.
+    /// Blocks until it acquires a shared lock.
+    pub(crate) fn version_of<Q>(&self, key: &Q) -> Option<u64>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
         self.cache
-            .write()
-            .expect("failed to acquire write lock")
-            .delete(key);
+            .read()
+            .expect("failed to acquire read lock")
+            .version_of(key)
+    }
+
+    /// Performs the given write operation under an exclusive lock, then notifies
+    /// the removal listener, if any, once the lock is released.
+    pub(crate) fn write<T, F>(&self, write: F) -> T
+    where
+        F: FnOnce(&mut Cache<K, V, P, S, C>) -> T,
+    {
+        let (result, removals) = {
+            let mut cache = self.cache.write().expect("failed to acquire write lock");
+            let result = write(&mut cache);
+            if let Some(reaper) = &*self.reaper() {
+                reaper.schedule(cache.next_expiration());
+            }
+
+            (result, cache.take_removals())
+        };
+
+        removals.notify();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{mpsc, Barrier, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::listener::RemovalCause;
+    use crate::CacheBuilder;
+
+    fn wait_until(condition: impl Fn() -> bool) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            if condition() {
+                return true;
+            }
+
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        false
+    }
+
+    #[test]
+    fn reaper_removes_expired_items() {
+        let mut cache = Cache::default();
+        cache.put_ttl("a", 1, Duration::from_secs(3_600));
+        cache.put_ttl("b", 2, Duration::from_millis(20));
+        cache.put("c", 3);
+
+        let cache = SyncCache::from(cache).with_reaper();
+
+        assert!(wait_until(|| cache.len() == 2));
+        assert_eq!(cache.get("a"), Some(1));
+        assert_eq!(cache.get("c"), Some(3));
+    }
+
+    #[test]
+    fn reaper_wakes_up_for_earlier_expirations() {
+        let cache = SyncCache::<&str, i32>::default().with_reaper();
+        cache.put_ttl("a", 1, Duration::from_secs(3_600));
+        cache.put_ttl("b", 2, Duration::from_millis(20));
+
+        assert!(wait_until(|| cache.len() == 1));
+        assert!(cache.contains_key("a"));
+    }
+
+    #[test]
+    fn reaper_is_woken_up_by_clones_made_before_it_started() {
+        let cache = SyncCache::<&str, i32>::default();
+        let clone = cache.clone();
+        let cache = cache.with_reaper();
+
+        clone.put_ttl("a", 1, Duration::from_millis(20));
+        assert!(wait_until(|| cache.is_empty()));
+    }
+
+    /// Value holding a clone of the cache storing it, if any, which records
+    /// whether it was dropped, and whether by a panicking thread.
+    #[derive(Clone)]
+    struct Holder {
+        _cache: Option<SyncCache<&'static str, Holder>>,
+        dropped: Arc<Mutex<Vec<bool>>>,
+    }
+
+    impl Drop for Holder {
+        fn drop(&mut self) {
+            self.dropped.lock().unwrap().push(thread::panicking());
+        }
+    }
+
+    #[test]
+    fn reaper_keeps_running_when_it_drops_the_last_clone() {
+        let cache = SyncCache::default().with_reaper();
+        let signal = cache.reaper().as_ref().unwrap().signal();
+        let dropped = Arc::new(Mutex::new(Vec::new()));
+        let ttl = Duration::from_millis(20);
+        let holder = Holder {
+            _cache: Some(cache.clone()),
+            dropped: dropped.clone(),
+        };
+        cache.put_ttl("a", holder, ttl);
+        let holder = Holder {
+            _cache: None,
+            dropped: dropped.clone(),
+        };
+        cache.put_ttl("b", holder, ttl);
+
+        // Removing the first item drops the last clone, on the thread itself.
+        drop(cache);
+        assert!(wait_until(|| dropped.lock().unwrap().len() == 2));
+        assert_eq!(*dropped.lock().unwrap(), vec![false, false]);
+        assert!(wait_until(|| signal.upgrade().is_none()));
+    }
+
+    #[test]
+    fn reaper_stops_when_last_clone_is_dropped() {
+        let cache = SyncCache::<&str, i32>::default().with_reaper();
+        cache.put_ttl("a", 1, Duration::from_secs(3_600));
+        let clone = cache.clone();
+        let weak = Arc::downgrade(&cache.cache);
+        let signal = cache.reaper().as_ref().unwrap().signal();
+
+        drop(clone);
+        assert!(weak.upgrade().is_some());
+        assert!(signal.upgrade().is_some());
+
+        drop(cache);
+        assert!(weak.upgrade().is_none());
+        // The thread holds the signal until it exits.
+        assert!(signal.upgrade().is_none());
+    }
+
+    #[test]
+    fn listener_runs_outside_write_lock() {
+        let shared = Arc::new(Mutex::new(None::<SyncCache<&str, i32>>));
+        let removed = Arc::new(Mutex::new(Vec::new()));
+        let cache = {
+            let shared = shared.clone();
+            let removed = removed.clone();
+            CacheBuilder::new()
+                .removal_listener(move |key, value, cause| {
+                    let shared = shared.lock().unwrap();
+                    let cache = shared.as_ref().unwrap();
+                    let len = cache.len();
+                    removed.lock().unwrap().push((key, value, cause, len));
+                })
+                .build_sync()
+        };
+
+        *shared.lock().unwrap() = Some(cache.clone());
+        cache.put("a", 1);
+        cache.put("a", 2);
+        cache.delete("a");
+        shared.lock().unwrap().take();
+
+        assert_eq!(
+            *removed.lock().unwrap(),
+            vec![
+                ("a", 1, RemovalCause::Replaced, 1),
+                ("a", 2, RemovalCause::Explicit, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn compute_increments_atomically() {
+        let cache = SyncCache::<&str, u64>::default();
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let cache = cache.clone();
+                thread::spawn(move || {
+                    for _ in 0..1_000 {
+                        cache.compute("counter", |count| {
+                            Op::Put(count.map_or(1, |count| count + 1))
+                        });
+                    }
+                })
+            })
+            .collect();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(cache.get("counter"), Some(4_000));
+    }
+
+    #[test]
+    fn conditional_updates_treat_expired_items_as_absent() {
+        let clock = ManualClock::new();
+        let cache = CacheBuilder::new().clock(clock.clone()).build_sync();
+        assert_eq!(cache.get_or_insert_with("a", || 1), 1);
+        assert_eq!(cache.get_or_insert_with("a", || unreachable!()), 1);
+        assert_eq!(cache.update("a", |value| value + 1), Some(2));
+        assert_eq!(cache.replace_if("a", |&value| value > 2, 10), Some(2));
+        assert_eq!(cache.replace_if("a", |&value| value == 2, 3), Some(3));
+        assert_eq!(cache.remove_if("a", |&value| value > 3), Some(3));
+        assert_eq!(cache.remove_if("a", |&value| value == 3), None);
+        assert_eq!(cache.update("a", |value| value + 1), None);
+
+        cache.put_ttl("b", 1, Duration::from_secs(1));
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(
+            cache.compute("b", |value| {
+                assert_eq!(value, None);
+                Op::PutExp(2, Some(clock.now() + Duration::from_secs(1)))
+            }),
+            Some(2)
+        );
+        assert_eq!(
+            cache.next_expiration(),
+            Some(clock.now() + Duration::from_secs(1))
+        );
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn get_or_load_coalesces_concurrent_loads() {
+        let cache = SyncCache::<&str, u64>::default();
+        let loads = Arc::new(AtomicUsize::new(0));
+        let barrier = Arc::new(Barrier::new(8));
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let cache = cache.clone();
+                let loads = loads.clone();
+                let barrier = barrier.clone();
+                thread::spawn(move || {
+                    barrier.wait();
+                    cache.get_or_load("a", |_| {
+                        loads.fetch_add(1, Ordering::SeqCst);
+                        thread::sleep(Duration::from_millis(200));
+                        Ok::<_, ()>(42)
+                    })
+                })
+            })
+            .collect();
+
+        for thread in threads {
+            assert_eq!(thread.join().unwrap(), Ok(42));
+        }
+
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+        assert_eq!(cache.get("a"), Some(42));
+    }
+
+    #[test]
+    fn get_or_load_records_load_time() {
+        let clock = ManualClock::new();
+        let cache = CacheBuilder::new()
+            .clock(clock.clone())
+            .default_ttl(Duration::from_secs(10))
+            .early_expiration(1.0)
+            .build_sync();
+
+        let load = |_: &&str| {
+            clock.advance(Duration::from_secs(1));
+            Ok::<_, ()>(42)
+        };
+        assert_eq!(cache.get_or_load("a", load), Ok(42));
+        cache.put("b", 42);
+
+        clock.advance(Duration::from_secs(10) - Duration::from_micros(1));
+        assert_eq!(cache.get_early("a"), Some((42, true)));
+        assert_eq!(cache.get_early("b"), Some((42, false)));
+    }
+
+    #[test]
+    fn get_or_load_propagates_errors_without_caching() {
+        let cache = SyncCache::<&str, u64>::default();
+        let loads = Arc::new(AtomicUsize::new(0));
+        let (started, start) = mpsc::channel();
+        let leader = {
+            let cache = cache.clone();
+            let loads = loads.clone();
+            thread::spawn(move || {
+                cache.get_or_load("a", |_| {
+                    loads.fetch_add(1, Ordering::SeqCst);
+                    started.send(()).unwrap();
+                    thread::sleep(Duration::from_millis(500));
+                    Err("unavailable".to_string())
+                })
+            })
+        };
+
+        start.recv().unwrap();
+        let follower = {
+            let cache = cache.clone();
+            let loads = loads.clone();
+            thread::spawn(move || {
+                cache.get_or_load("a", |_| {
+                    loads.fetch_add(1, Ordering::SeqCst);
+                    Ok(1)
+                })
+            })
+        };
+
+        assert_eq!(leader.join().unwrap(), Err("unavailable".to_string()));
+        assert_eq!(follower.join().unwrap(), Err("unavailable".to_string()));
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+        assert_eq!(cache.get("a"), None);
+
+        assert_eq!(cache.get_or_load("a", |_| Ok::<_, String>(2)), Ok(2));
+        assert_eq!(cache.get("a"), Some(2));
+    }
+
+    #[test]
+    fn get_or_load_recovers_from_panicking_loader() {
+        let cache = SyncCache::<&str, u64>::default();
+        let result = {
+            let cache = cache.clone();
+            thread::spawn(move || cache.get_or_load("a", |_| -> Result<u64, ()> { panic!("boom") }))
+                .join()
+        };
+
+        assert!(result.is_err());
+        assert_eq!(cache.get_or_load("a", |_| Ok::<_, ()>(1)), Ok(1));
     }
 }
@@ -0,0 +1,50 @@
+//! Weighing of cache entries for weight-bounded caches.
+
+use std::fmt;
+
+/// Computes the weight of a cache entry, such as its approximate size in bytes.
+///
+/// Any closure taking the key and value by reference and returning
+/// the weight implements this trait.
+pub trait Weigher<K, V> {
+    /// Returns the weight of the given entry.
+    fn weigh(&self, key: &K, value: &V) -> u64;
+}
+
+impl<K, V, F: Fn(&K, &V) -> u64> Weigher<K, V> for F {
+    fn weigh(&self, key: &K, value: &V) -> u64 {
+        self(key, value)
+    }
+}
+
+/// Weight bound of a cache, along with the running total weight of its entries.
+pub(crate) struct Weighing<K, V> {
+    pub(crate) weigher: Box<dyn Weigher<K, V> + Send + Sync>,
+    pub(crate) max_weight: u64,
+    pub(crate) total_weight: u64,
+    pub(crate) reject_oversized: bool,
+}
+
+impl<K, V> Weighing<K, V> {
+    pub(crate) fn new<W>(max_weight: u64, weigher: W) -> Self
+    where
+        W: Weigher<K, V> + Send + Sync + 'static,
+    {
+        Self {
+            weigher: Box::new(weigher),
+            max_weight,
+            total_weight: 0,
+            reject_oversized: true,
+        }
+    }
+}
+
+impl<K, V> fmt::Debug for Weighing<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Weighing")
+            .field("max_weight", &self.max_weight)
+            .field("total_weight", &self.total_weight)
+            .field("reject_oversized", &self.reject_oversized)
+            .finish()
+    }
+}
@@ -0,0 +1,257 @@
+//! Hierarchical timing wheel tracking the expiration of slots.
+
+use crate::list::Lists;
+
+/// Number of low bits of a stamp (in nanoseconds) below the wheel's resolution,
+/// so that a tick lasts about a millisecond.
+const TICK_BITS: u32 = 20;
+
+/// Number of bits of a tick resolved by each level of the wheel.
+const SLOT_BITS: u32 = 6;
+
+const SLOTS: usize = 1 << SLOT_BITS;
+
+/// Number of levels needed to cover every tick of a 64-bit stamp.
+const LEVELS: usize = 8;
+
+/// List of slots whose expiration tick had already elapsed when they were scheduled.
+const OVERDUE: usize = LEVELS * SLOTS;
+
+/// Hierarchical timing wheel, scheduling and cancelling expirations in O(1).
+///
+/// Each level holds 64 buckets spanning 64 times as many ticks as those of the level
+/// below. A slot is placed in the lowest level whose range includes its expiration tick,
+/// relative to the current tick; as the wheel advances past the start of a bucket,
+/// the bucket's slots are either expired or cascaded down to a lower level.
+/// Since each slot cascades at most once per level, advancement is amortized O(1).
+#[derive(Debug)]
+pub(crate) struct TimingWheel {
+    lists: Lists,
+    ticks: Vec<u64>,
+    occupied: [u64; LEVELS],
+    elapsed: u64,
+}
+
+impl Default for TimingWheel {
+    fn default() -> Self {
+        Self {
+            lists: Lists::new(OVERDUE + 1),
+            ticks: Vec::new(),
+            occupied: [0; LEVELS],
+            elapsed: 0,
+        }
+    }
+}
+
+impl TimingWheel {
+    /// Schedules the slot to expire at the given stamp. Expiration is rounded up
+    /// to the next tick, so the slot never expires early.
+    pub(crate) fn schedule(&mut self, slot: usize, stamp: u64) {
+        let tick = (stamp >> TICK_BITS) + u64::from(stamp & ((1 << TICK_BITS) - 1) != 0);
+        if slot >= self.ticks.len() {
+            self.ticks.resize(slot + 1, 0);
+        }
+
+        self.ticks[slot] = tick;
+        self.link(slot, tick);
+    }
+
+    /// Cancels the scheduled expiration of the slot, if any.
+    pub(crate) fn cancel(&mut self, slot: usize) {
+        if let Some(list) = self.lists.remove(slot) {
+            if list != OVERDUE && self.lists.len(list) == 0 {
+                self.occupied[list / SLOTS] &= !(1 << (list % SLOTS));
+            }
+        }
+    }
+
+    /// Advances the wheel towards the given stamp, appending the slots that expired
+    /// by then to `expired`. Stops after processing `limit` slots, whether expired or
+    /// cascaded, and returns whether the wheel caught up with the stamp.
+    pub(crate) fn advance(&mut self, stamp: u64, limit: usize, expired: &mut Vec<usize>) -> bool {
+        let now = stamp >> TICK_BITS;
+        let mut remaining = limit;
+        while remaining > 0 {
+            if let Some(slot) = self.lists.pop_front(OVERDUE) {
+                expired.push(slot);
+                remaining -= 1;
+                continue;
+            }
+
+            let (level, index, start) = match self.next_bucket() {
+                Some(bucket) if bucket.2 <= now => bucket,
+                _ => {
+                    self.elapsed = self.elapsed.max(now);
+                    return true;
+                }
+            };
+
+            // A bucket left partially processed starts at the current tick,
+            // so it is resumed before any other bucket.
+            self.elapsed = start;
+            let list = level * SLOTS + index;
+            while remaining > 0 {
+                let slot = match self.lists.pop_front(list) {
+                    Some(slot) => slot,
+                    None => break,
+                };
+
+                remaining -= 1;
+                let tick = self.ticks[slot];
+                if tick <= now {
+                    expired.push(slot);
+                } else {
+                    self.link(slot, tick);
+                }
+            }
+
+            if self.lists.len(list) == 0 {
+                self.occupied[level] &= !(1 << index);
+            }
+        }
+
+        false
+    }
+
+    /// Returns a stamp no later than the earliest scheduled expiration, if any.
+    /// The stamp is exact to the tick for expirations on the lowest level, and
+    /// the start of the bucket containing the expiration otherwise.
+    pub(crate) fn next_expiration(&self) -> Option<u64> {
+        if self.lists.len(OVERDUE) > 0 {
+            return Some(self.elapsed << TICK_BITS);
+        }
+
+        self.next_bucket().map(|(_, _, start)| start << TICK_BITS)
+    }
+
+    /// Returns the number of scheduled slots.
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        (0..=OVERDUE).map(|list| self.lists.len(list)).sum()
+    }
+
+    fn link(&mut self, slot: usize, tick: u64) {
+        if tick <= self.elapsed {
+            self.lists.push_back(OVERDUE, slot);
+            return;
+        }
+
+        // The level is determined by the most significant bit in which
+        // the tick differs from the current one.
+        let significant = 63 - ((self.elapsed ^ tick) | (SLOTS as u64 - 1)).leading_zeros();
+        let level = (significant / SLOT_BITS) as usize;
+        let index = ((tick >> (level as u32 * SLOT_BITS)) as usize) & (SLOTS - 1);
+        self.occupied[level] |= 1 << index;
+        self.lists.push_back(level * SLOTS + index, slot);
+    }
+
+    /// Returns the level, index, and starting tick of the earliest occupied bucket.
+    ///
+    /// Every occupied bucket lies beyond the current tick's bucket on its level,
+    /// except for one left partially processed, which starts at the current tick.
+    fn next_bucket(&self) -> Option<(usize, usize, u64)> {
+        (0..LEVELS)
+            .filter(|&level| self.occupied[level] != 0)
+            .map(|level| {
+                let index = self.occupied[level].trailing_zeros() as usize;
+                let shift = level as u32 * SLOT_BITS;
+                let level_start = self.elapsed & !((1 << (shift + SLOT_BITS)) - 1);
+                (level, index, level_start + ((index as u64) << shift))
+            })
+            .min_by_key(|&(_, _, start)| start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ticks(count: u64) -> u64 {
+        count << TICK_BITS
+    }
+
+    fn advance(wheel: &mut TimingWheel, stamp: u64) -> Vec<usize> {
+        let mut expired = Vec::new();
+        assert!(wheel.advance(stamp, usize::MAX, &mut expired));
+        expired
+    }
+
+    #[test]
+    fn expires_slots_across_levels() {
+        let mut wheel = TimingWheel::default();
+        wheel.schedule(0, ticks(5));
+        wheel.schedule(1, ticks(5_000));
+        wheel.schedule(2, ticks(80_000_000));
+
+        assert_eq!(advance(&mut wheel, ticks(4)), vec![]);
+        assert_eq!(advance(&mut wheel, ticks(5)), vec![0]);
+        assert_eq!(advance(&mut wheel, ticks(4_999)), vec![]);
+        assert_eq!(advance(&mut wheel, ticks(5_000)), vec![1]);
+        assert_eq!(advance(&mut wheel, ticks(79_999_999)), vec![]);
+        assert_eq!(advance(&mut wheel, ticks(90_000_000)), vec![2]);
+        assert_eq!(wheel.len(), 0);
+    }
+
+    #[test]
+    fn rounds_expiration_up_to_next_tick() {
+        let mut wheel = TimingWheel::default();
+        wheel.schedule(0, ticks(3) + 1);
+
+        assert_eq!(advance(&mut wheel, ticks(3) + 2), vec![]);
+        assert_eq!(advance(&mut wheel, ticks(4)), vec![0]);
+    }
+
+    #[test]
+    fn cancels_scheduled_slots() {
+        let mut wheel = TimingWheel::default();
+        wheel.schedule(0, ticks(100));
+        wheel.schedule(1, ticks(100));
+        wheel.cancel(0);
+
+        assert_eq!(advance(&mut wheel, ticks(200)), vec![1]);
+        assert_eq!(wheel.occupied, [0; LEVELS]);
+    }
+
+    #[test]
+    fn expires_overdue_slots_immediately() {
+        let mut wheel = TimingWheel::default();
+        advance(&mut wheel, ticks(1_000));
+        wheel.schedule(0, ticks(10));
+        wheel.schedule(1, ticks(1_001));
+
+        assert_eq!(advance(&mut wheel, ticks(1_000)), vec![0]);
+        assert_eq!(advance(&mut wheel, ticks(1_001)), vec![1]);
+    }
+
+    #[test]
+    fn advances_incrementally() {
+        let mut wheel = TimingWheel::default();
+        for slot in 0..5 {
+            wheel.schedule(slot, ticks(100));
+        }
+
+        wheel.schedule(5, ticks(10_000));
+
+        let mut expired = Vec::new();
+        assert!(!wheel.advance(ticks(100), 3, &mut expired));
+        assert_eq!(expired, vec![0, 1, 2]);
+
+        wheel.schedule(6, ticks(50));
+        assert!(wheel.advance(ticks(100), 4, &mut expired));
+        assert_eq!(expired, vec![0, 1, 2, 6, 3, 4]);
+        assert_eq!(wheel.len(), 1);
+    }
+
+    #[test]
+    fn reports_next_expiration() {
+        let mut wheel = TimingWheel::default();
+        assert_eq!(wheel.next_expiration(), None);
+
+        wheel.schedule(0, ticks(5_000));
+        wheel.schedule(1, ticks(30));
+        assert_eq!(wheel.next_expiration(), Some(ticks(30)));
+
+        advance(&mut wheel, ticks(30));
+        assert_eq!(wheel.next_expiration(), Some(ticks(4_096)));
+    }
+}